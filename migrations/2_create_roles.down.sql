@@ -0,0 +1,9 @@
+DROP TABLE "user_roles";
+DROP TABLE "role_permissions";
+DROP TABLE "permissions";
+DROP TABLE "roles";
+
+DROP FUNCTION bump_user_authz_version();
+DROP FUNCTION bump_role_authz_version();
+
+ALTER TABLE "users" DROP COLUMN authz_version;
@@ -0,0 +1,70 @@
+CREATE TABLE "roles" (
+    role_id uuid primary key default gen_random_uuid(),
+    name text unique not null
+);
+
+CREATE TABLE "permissions" (
+    permission_id uuid primary key default gen_random_uuid(),
+    name text unique not null
+);
+
+CREATE TABLE "role_permissions" (
+    role_id uuid not null references roles(role_id) on delete cascade,
+    permission_id uuid not null references permissions(permission_id) on delete cascade,
+    primary key (role_id, permission_id)
+);
+
+CREATE TABLE "user_roles" (
+    user_id uuid not null references users(user_id) on delete cascade,
+    role_id uuid not null references roles(role_id) on delete cascade,
+    primary key (user_id, role_id)
+);
+
+-- Bumped whenever the roles or permissions granted to a user change, so
+-- sessions holding a stale copy of them know to reload
+ALTER TABLE "users" ADD COLUMN authz_version bigint not null default 0;
+
+CREATE FUNCTION bump_user_authz_version() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') THEN
+        UPDATE users SET authz_version = authz_version + 1 WHERE user_id = OLD.user_id;
+    END IF;
+    IF TG_OP IN ('INSERT', 'UPDATE') THEN
+        UPDATE users SET authz_version = authz_version + 1 WHERE user_id = NEW.user_id;
+    END IF;
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER user_roles_bump_authz_version
+AFTER INSERT OR UPDATE OR DELETE ON "user_roles"
+FOR EACH ROW EXECUTE FUNCTION bump_user_authz_version();
+
+CREATE FUNCTION bump_role_authz_version() RETURNS trigger AS $$
+BEGIN
+    UPDATE users SET authz_version = authz_version + 1
+    WHERE user_id IN (
+        SELECT user_id FROM user_roles
+        WHERE role_id IN (
+            CASE WHEN TG_OP = 'INSERT' THEN NULL ELSE OLD.role_id END,
+            CASE WHEN TG_OP = 'DELETE' THEN NULL ELSE NEW.role_id END
+        )
+    );
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER role_permissions_bump_authz_version
+AFTER INSERT OR UPDATE OR DELETE ON "role_permissions"
+FOR EACH ROW EXECUTE FUNCTION bump_role_authz_version();
+
+INSERT INTO "permissions"(name)
+values ('users:read'), ('users:write'), ('admin:access');
+
+INSERT INTO "roles"(name)
+values ('admin'), ('researcher');
+
+INSERT INTO "role_permissions"(role_id, permission_id)
+SELECT role_id, permission_id FROM roles, permissions
+WHERE roles.name = 'admin'
+   OR (roles.name = 'researcher' AND permissions.name = 'users:read');
@@ -0,0 +1,3 @@
+ALTER TABLE "users"
+    ADD COLUMN disabled boolean not null default false,
+    ADD COLUMN password_reset_required boolean not null default false;
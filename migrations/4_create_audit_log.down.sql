@@ -0,0 +1,4 @@
+DELETE FROM "permissions" WHERE name IN ('users:impersonate', 'audit:read');
+
+DROP TABLE "audit_log";
+DROP FUNCTION audit_log_append_only();
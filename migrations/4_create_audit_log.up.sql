@@ -0,0 +1,38 @@
+-- Deliberately has no foreign keys, entries must outlive the users they
+-- mention.
+--
+-- Each entry is hash-chained to the one before it by the app, with a key the
+-- database never sees so rewriting the log takes more than write access to
+-- it. Ids are handed out by the app too, under the same lock as the chain, so
+-- that id order always matches chain order.
+CREATE TABLE "audit_log" (
+    audit_id bigint primary key,
+    occurred_at timestamptz not null,
+    action text not null,
+    actor_id uuid,
+    target_id uuid,
+    details jsonb,
+    prev_hash bytea,
+    hash bytea not null
+);
+
+CREATE FUNCTION audit_log_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_log is append-only';
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_append_only
+BEFORE UPDATE OR DELETE OR TRUNCATE ON "audit_log"
+FOR EACH STATEMENT EXECUTE FUNCTION audit_log_append_only();
+
+CREATE INDEX audit_log_actor_id_idx ON "audit_log"(actor_id);
+CREATE INDEX audit_log_target_id_idx ON "audit_log"(target_id);
+CREATE INDEX audit_log_occurred_at_idx ON "audit_log"(occurred_at);
+
+INSERT INTO "permissions"(name)
+values ('users:impersonate'), ('audit:read');
+
+INSERT INTO "role_permissions"(role_id, permission_id)
+SELECT role_id, permission_id FROM roles, permissions
+WHERE roles.name = 'admin' AND permissions.name IN ('users:impersonate', 'audit:read');
@@ -0,0 +1,419 @@
+use std::{fmt, net::IpAddr};
+
+use sqlx::{Acquire, PgPool, Postgres};
+
+use serde_json::json;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+// Number of entries fetched at a time while walking the hash chain
+const VERIFY_BATCH_SIZE: i64 = 1000;
+// Set once and for all, changing it would change every key derived with it
+const KEY_CONTEXT: &str = "mindtrails 2026-10-19 audit log hash chain";
+
+/// Whoever caused an event that was done to someone else's account
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Actor
+{
+    User(Uuid),
+    /// Someone with shell access running the `mindtrails` command line
+    Operator,
+}
+
+/// Where the request that caused an event came from, kept in the event's
+/// details
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Client
+{
+    pub(crate) ip: Option<IpAddr>,
+    pub(crate) user_agent: Option<String>,
+}
+
+/// A security-relevant event, recorded in the append-only `audit_log` table.
+///
+/// Each entry is hash-chained to the one before it with a key the database
+/// doesn't have, see `Log` for recording events and detecting tampering.
+#[derive(Debug, Clone)]
+pub(crate) enum AuditEvent
+{
+    UserCreated
+    {
+        user_id: Uuid,
+    },
+    LoginSucceeded
+    {
+        user_id: Uuid,
+    },
+    LoginFailed
+    {
+        user_id: Uuid,
+    },
+    LoggedOut
+    {
+        user_id: Uuid,
+    },
+    PasswordChanged
+    {
+        user_id: Uuid,
+    },
+    PasswordResetForced
+    {
+        actor: Actor,
+        user_id: Uuid,
+    },
+    AccountDisabled
+    {
+        actor: Actor,
+        user_id: Uuid,
+    },
+    AccountEnabled
+    {
+        actor: Actor,
+        user_id: Uuid,
+    },
+    SessionsRevoked
+    {
+        actor: Actor,
+        user_id: Uuid,
+        count: usize,
+    },
+    RoleGranted
+    {
+        actor: Actor,
+        user_id: Uuid,
+        role: String,
+    },
+    RoleRevoked
+    {
+        actor: Actor,
+        user_id: Uuid,
+        role: String,
+    },
+    ImpersonationStarted
+    {
+        actor_id: Uuid,
+        user_id: Uuid,
+    },
+    ImpersonationEnded
+    {
+        actor_id: Uuid,
+        user_id: Option<Uuid>,
+    },
+}
+
+impl AuditEvent
+{
+    pub(crate) fn action(&self) -> &'static str
+    {
+        match self {
+            AuditEvent::UserCreated { .. } => "user.created",
+            AuditEvent::LoginSucceeded { .. } => "auth.login_succeeded",
+            AuditEvent::LoginFailed { .. } => "auth.login_failed",
+            AuditEvent::LoggedOut { .. } => "auth.logged_out",
+            AuditEvent::PasswordChanged { .. } => "user.password_changed",
+            AuditEvent::PasswordResetForced { .. } => "user.password_reset_forced",
+            AuditEvent::AccountDisabled { .. } => "user.disabled",
+            AuditEvent::AccountEnabled { .. } => "user.enabled",
+            AuditEvent::SessionsRevoked { .. } => "session.revoked",
+            AuditEvent::RoleGranted { .. } => "role.granted",
+            AuditEvent::RoleRevoked { .. } => "role.revoked",
+            AuditEvent::ImpersonationStarted { .. } => "impersonation.started",
+            AuditEvent::ImpersonationEnded { .. } => "impersonation.ended",
+        }
+    }
+
+    fn actor(&self) -> Option<Actor>
+    {
+        match self {
+            AuditEvent::UserCreated { .. }
+            | AuditEvent::LoginSucceeded { .. }
+            | AuditEvent::LoginFailed { .. }
+            | AuditEvent::LoggedOut { .. }
+            | AuditEvent::PasswordChanged { .. } => None,
+            AuditEvent::PasswordResetForced { actor, .. }
+            | AuditEvent::AccountDisabled { actor, .. }
+            | AuditEvent::AccountEnabled { actor, .. }
+            | AuditEvent::SessionsRevoked { actor, .. }
+            | AuditEvent::RoleGranted { actor, .. }
+            | AuditEvent::RoleRevoked { actor, .. } => Some(*actor),
+            AuditEvent::ImpersonationStarted { actor_id, .. }
+            | AuditEvent::ImpersonationEnded { actor_id, .. } => Some(Actor::User(*actor_id)),
+        }
+    }
+
+    /// Who caused the event, `None` if it was the target themselves or an
+    /// operator
+    fn actor_id(&self) -> Option<Uuid>
+    {
+        match self.actor()? {
+            Actor::User(user_id) => Some(user_id),
+            Actor::Operator => None,
+        }
+    }
+
+    fn target_id(&self) -> Option<Uuid>
+    {
+        match self {
+            AuditEvent::UserCreated { user_id }
+            | AuditEvent::LoginSucceeded { user_id }
+            | AuditEvent::LoginFailed { user_id }
+            | AuditEvent::LoggedOut { user_id }
+            | AuditEvent::PasswordChanged { user_id }
+            | AuditEvent::PasswordResetForced { user_id, .. }
+            | AuditEvent::AccountDisabled { user_id, .. }
+            | AuditEvent::AccountEnabled { user_id, .. }
+            | AuditEvent::SessionsRevoked { user_id, .. }
+            | AuditEvent::RoleGranted { user_id, .. }
+            | AuditEvent::RoleRevoked { user_id, .. }
+            | AuditEvent::ImpersonationStarted { user_id, .. } => Some(*user_id),
+            AuditEvent::ImpersonationEnded { user_id, .. } => *user_id,
+        }
+    }
+
+    fn details(&self, client: Option<&Client>) -> Option<serde_json::Value>
+    {
+        let mut details = match self {
+            AuditEvent::SessionsRevoked { count, .. } => json!({ "count": count }),
+            AuditEvent::RoleGranted { role, .. } | AuditEvent::RoleRevoked { role, .. } => {
+                json!({ "role": role })
+            }
+            _ => json!({}),
+        };
+
+        // Tells these apart from events the target caused themselves, which
+        // have no actor either
+        if self.actor() == Some(Actor::Operator) {
+            details["via"] = json!("cli");
+        }
+        if let Some(ip) = client.and_then(|client| client.ip) {
+            details["ip"] = json!(ip);
+        }
+        if let Some(user_agent) = client.and_then(|client| client.user_agent.as_deref()) {
+            details["user_agent"] = json!(user_agent);
+        }
+
+        match details.as_object() {
+            Some(fields) if fields.is_empty() => None,
+            _ => Some(details),
+        }
+    }
+}
+
+/// Records events in the audit log, chaining each entry to the one before it
+/// with a keyed hash.
+///
+/// The key is kept out of the database, so rewriting entries takes more than
+/// write access to it: without the key the hashes can't be made to match
+/// again, which `verify` catches.
+#[derive(Clone)]
+pub(crate) struct Log
+{
+    key: [u8; blake3::KEY_LEN],
+}
+
+impl fmt::Debug for Log
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        f.debug_struct("Log").finish_non_exhaustive()
+    }
+}
+
+impl Log
+{
+    /// A log chained with a key derived from `secret`, see `audit.key`
+    pub(crate) fn new(secret: &str) -> Self
+    {
+        Log {
+            key: blake3::derive_key(KEY_CONTEXT, secret.as_bytes()),
+        }
+    }
+
+    /// Appends `event` to the audit log, pass a transaction as `db` to have
+    /// the entry commit or roll back together with the change it describes.
+    ///
+    /// `client` is `None` for events that didn't come from a request.
+    pub(crate) async fn record<'c, A>(
+        &self,
+        db: A,
+        event: AuditEvent,
+        client: Option<&Client>,
+    ) -> Result<(), sqlx::Error>
+    where
+        A: Acquire<'c, Database = Postgres>,
+    {
+        let mut transaction = db.begin().await?;
+
+        // Serializes appends until the transaction ends, so that every entry
+        // links to the one committed before it
+        let _pg_query_res = sqlx::query("SELECT pg_advisory_xact_lock(hashtext('audit_log'))")
+            .execute(&mut transaction)
+            .await?;
+
+        let last =
+            sqlx::query!(r#"SELECT audit_id, hash FROM audit_log ORDER BY audit_id DESC LIMIT 1"#)
+                .fetch_optional(&mut transaction)
+                .await?;
+
+        let entry = Entry {
+            audit_id: last.as_ref().map_or(1, |last| last.audit_id + 1),
+            // Postgres keeps timestamps to the microsecond, the hash has to be
+            // of what it keeps
+            occurred_at: truncate_to_micros(OffsetDateTime::now_utc()),
+            action: String::from(event.action()),
+            actor_id: event.actor_id(),
+            target_id: event.target_id(),
+            details: event.details(client),
+            prev_hash: last.map(|last| last.hash),
+        };
+        let hash = self.hash(&entry);
+
+        let _pg_query_res = sqlx::query!(
+            r#"
+                INSERT INTO "audit_log"(
+                    audit_id, occurred_at, action, actor_id, target_id, details, prev_hash, hash
+                )
+                values ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            entry.audit_id,
+            entry.occurred_at,
+            entry.action,
+            entry.actor_id,
+            entry.target_id,
+            entry.details,
+            entry.prev_hash,
+            hash.as_bytes().as_slice()
+        )
+        .execute(&mut transaction)
+        .await?;
+
+        transaction.commit().await
+    }
+
+    /// Walks the whole hash chain in order, recomputing each entry's hash
+    pub(crate) async fn verify(&self, pg_pool: &PgPool) -> Result<Verification, sqlx::Error>
+    {
+        let mut entries = 0;
+        let mut last: Option<(i64, Vec<u8>)> = None;
+
+        loop {
+            let after = last.as_ref().map_or(0, |(audit_id, _hash)| *audit_id);
+
+            let batch = sqlx::query!(
+                r#"
+                    SELECT
+                        audit_id,
+                        occurred_at,
+                        action,
+                        actor_id,
+                        target_id,
+                        details,
+                        prev_hash,
+                        hash
+                    FROM audit_log
+                    WHERE audit_id > $1
+                    ORDER BY audit_id
+                    LIMIT $2
+                "#,
+                after,
+                VERIFY_BATCH_SIZE
+            )
+            .fetch_all(pg_pool)
+            .await?;
+
+            if batch.is_empty() {
+                return Ok(Verification::Intact { entries });
+            }
+
+            for row in batch {
+                let entry = Entry {
+                    audit_id: row.audit_id,
+                    occurred_at: row.occurred_at,
+                    action: row.action,
+                    actor_id: row.actor_id,
+                    target_id: row.target_id,
+                    details: row.details,
+                    prev_hash: row.prev_hash,
+                };
+
+                let links_to_last = match &last {
+                    Some((audit_id, hash)) => {
+                        entry.audit_id == audit_id + 1 && entry.prev_hash.as_ref() == Some(hash)
+                    }
+                    None => entry.audit_id == 1 && entry.prev_hash.is_none(),
+                };
+                // Compared in constant time, so the expected hash can't be
+                // worked out a byte at a time
+                let hash_matches = <[u8; blake3::OUT_LEN]>::try_from(row.hash.as_slice())
+                    .is_ok_and(|hash| self.hash(&entry) == blake3::Hash::from(hash));
+
+                if !links_to_last || !hash_matches {
+                    return Ok(Verification::Broken {
+                        audit_id: entry.audit_id,
+                    });
+                }
+
+                entries += 1;
+                last = Some((entry.audit_id, row.hash));
+            }
+        }
+    }
+
+    fn hash(&self, entry: &Entry) -> blake3::Hash
+    {
+        let occurred_at_micros = entry.occurred_at.unix_timestamp() * 1_000_000
+            + i64::from(entry.occurred_at.microsecond());
+        // A JSON array, so no field can run into the next one
+        let fields = json!([
+            entry.audit_id,
+            occurred_at_micros,
+            entry.action,
+            entry.actor_id,
+            entry.target_id,
+            entry.details,
+        ]);
+
+        let mut hasher = blake3::Hasher::new_keyed(&self.key);
+        let _hasher = hasher
+            .update(entry.prev_hash.as_deref().unwrap_or_default())
+            .update(fields.to_string().as_bytes());
+
+        hasher.finalize()
+    }
+}
+
+/// What an entry's hash covers, everything but the hash itself
+#[derive(Debug)]
+struct Entry
+{
+    audit_id: i64,
+    occurred_at: OffsetDateTime,
+    action: String,
+    actor_id: Option<Uuid>,
+    target_id: Option<Uuid>,
+    details: Option<serde_json::Value>,
+    prev_hash: Option<Vec<u8>>,
+}
+
+fn truncate_to_micros(timestamp: OffsetDateTime) -> OffsetDateTime
+{
+    // SAFETY: Any microsecond is a valid one
+    timestamp
+        .replace_microsecond(timestamp.microsecond())
+        .unwrap()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Verification
+{
+    Intact
+    {
+        entries: u64,
+    },
+    /// The entry with the given id doesn't hash to what was recorded, or
+    /// doesn't link to the entry before it
+    Broken
+    {
+        audit_id: i64,
+    },
+}
@@ -0,0 +1,113 @@
+use std::mem;
+
+use sqlx::migrate::{Migrate, Migrator};
+
+use crate::{cli, config::Config};
+
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Reverting the migration creating the users drops every account along with
+/// everything that refers to them
+const BASELINE_VERSION: i64 = 1;
+
+#[derive(Debug)]
+pub(in crate::cli) enum Command
+{
+    Up,
+    Status,
+    Revert
+    {
+        /// Whether reverting the baseline migration is intended
+        yes: bool,
+    },
+}
+
+impl Command
+{
+    pub(in crate::cli) fn parse(
+        args: &[&str],
+        options: &mut cli::Options,
+    ) -> Result<Self, cli::Error>
+    {
+        match args {
+            ["up"] => Ok(Command::Up),
+            ["status"] => Ok(Command::Status),
+            ["revert"] => Ok(Command::Revert {
+                yes: mem::take(&mut options.yes),
+            }),
+            _ => Err(cli::Error::UnknownCommand {
+                command: format!("migrate {}", args.join(" ")),
+            }),
+        }
+    }
+
+    pub(in crate::cli) async fn run(self, config: &Config) -> Result<(), cli::Error>
+    {
+        let pg_pool = cli::connect_postgres(config).await?;
+
+        match self {
+            Command::Up => {
+                MIGRATOR.run(&pg_pool).await?;
+                println!("all migrations are applied");
+            }
+            Command::Status => {
+                let mut connection = pg_pool.acquire().await?;
+                connection.ensure_migrations_table().await?;
+
+                let dirty_version = connection.dirty_version().await?;
+                let applied = connection.list_applied_migrations().await?;
+
+                for migration in up_migrations() {
+                    let status = if dirty_version == Some(migration.version) {
+                        "partially applied"
+                    } else if applied.iter().any(|m| m.version == migration.version) {
+                        "applied"
+                    } else {
+                        "pending"
+                    };
+                    println!(
+                        "{:>4}  {:<18}  {}",
+                        migration.version, status, migration.description
+                    );
+                }
+            }
+            Command::Revert { yes } => {
+                let mut connection = pg_pool.acquire().await?;
+                connection.ensure_migrations_table().await?;
+
+                let mut applied = connection
+                    .list_applied_migrations()
+                    .await?
+                    .into_iter()
+                    .map(|migration| migration.version)
+                    .collect::<Vec<_>>();
+                applied.sort_unstable();
+
+                match applied.pop() {
+                    Some(BASELINE_VERSION) if !yes => Err(cli::Error::RevertBaseline {
+                        version: BASELINE_VERSION,
+                    })?,
+                    Some(latest) => {
+                        // Everything after the one before the latest, which
+                        // is just the latest
+                        let target = applied.last().copied().unwrap_or(0);
+                        MIGRATOR.undo(&pg_pool, target).await?;
+                        println!("reverted migration {latest}");
+                    }
+                    None => println!("no migrations are applied"),
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Each reversible migration is listed twice by the migrator, once for each
+/// direction
+fn up_migrations() -> impl Iterator<Item = &'static sqlx::migrate::Migration>
+{
+    MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+}
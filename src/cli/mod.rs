@@ -0,0 +1,370 @@
+//! The `mindtrails` command line, serving the app by default and offering
+//! management commands to operators with shell access
+
+use std::{
+    env, fmt,
+    io::{self, BufRead, IsTerminal, Write},
+    path::PathBuf,
+};
+
+use sqlx::{postgres::PgPoolOptions, PgPool};
+
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{
+    config::{self, Config},
+    http::{self, session},
+    password::{self, breach},
+};
+
+mod migrate;
+mod serve;
+mod sessions;
+mod user;
+
+const USAGE: &str = "\
+usage: mindtrails [--config <path>] [command]
+
+commands:
+    serve                                 serve the app (the default)
+    migrate up                            apply pending migrations
+    migrate status                        list migrations and whether they are applied
+    migrate revert [--yes]                revert the latest applied migration, `--yes` is
+                                          needed for the first one as it drops every account
+    user create <username> [--role <role>]
+                                          create a user, reading the password from stdin
+    user disable <user>                   disable a user and log them out
+    user reset-password <user>            set a temporary password read from stdin,
+                                          which has to be changed on the next login
+    sessions list --user <user>           list the sessions of a user
+    sessions purge (--user <user> | --all)
+                                          log a user, or everyone, out
+    config check                          validate the config and print it with secrets
+                                          redacted, `--print-config` does the same
+
+<user> is either a username or a user id. The config file can also be given
+with `CONFIG_FILE=<path>`.
+";
+
+#[derive(Debug)]
+struct Args
+{
+    config_path: Option<PathBuf>,
+    command: Command,
+}
+
+#[derive(Debug)]
+enum Command
+{
+    Help,
+    Serve,
+    Migrate(migrate::Command),
+    User(user::Command),
+    Sessions(sessions::Command),
+    ConfigCheck,
+}
+
+/// Options that only some commands take, each command takes out the ones it
+/// uses so that any left over can be reported
+#[derive(Debug, Default)]
+struct Options
+{
+    user: Option<String>,
+    role: Option<String>,
+    all: bool,
+    yes: bool,
+    /// What `config check` was before there were commands
+    print_config: bool,
+}
+
+impl Args
+{
+    fn parse(raw_args: impl IntoIterator<Item = String>) -> Result<Self, self::Error>
+    {
+        let mut config_path = None;
+        let mut options = Options::default();
+        let mut words = Vec::new();
+        let mut help = false;
+
+        let mut raw_args = raw_args.into_iter();
+        while let Some(arg) = raw_args.next() {
+            let mut value = || {
+                raw_args
+                    .next()
+                    .ok_or(Error::MissingArgValue { arg: arg.clone() })
+            };
+
+            match arg.as_str() {
+                "--config" => config_path = Some(PathBuf::from(value()?)),
+                "--user" => options.user = Some(value()?),
+                "--role" => options.role = Some(value()?),
+                "--all" => options.all = true,
+                "--yes" => options.yes = true,
+                "--print-config" => options.print_config = true,
+                "-h" | "--help" => help = true,
+                _ if arg.starts_with('-') => Err(Error::UnknownArg { arg })?,
+                _ => words.push(arg),
+            }
+        }
+
+        let mut words = words.iter().map(String::as_str).collect::<Vec<_>>();
+        if words.is_empty() && options.print_config {
+            options.print_config = false;
+            words = vec!["config", "check"];
+        }
+
+        let command = match words.as_slice() {
+            _ if help => Command::Help,
+            [] | ["serve"] => Command::Serve,
+            ["help"] => Command::Help,
+            ["migrate", args @ ..] => {
+                Command::Migrate(migrate::Command::parse(args, &mut options)?)
+            }
+            ["user", args @ ..] => Command::User(user::Command::parse(args, &mut options)?),
+            ["sessions", args @ ..] => {
+                Command::Sessions(sessions::Command::parse(args, &mut options)?)
+            }
+            ["config", "check"] => Command::ConfigCheck,
+            _ => Err(Error::UnknownCommand {
+                command: words.join(" "),
+            })?,
+        };
+
+        if let Some(arg) = options.leftover() {
+            Err(Error::UnusedArg { arg })?;
+        }
+
+        let config_path = config_path.or_else(|| env::var_os("CONFIG_FILE").map(PathBuf::from));
+
+        Ok(Args {
+            config_path,
+            command,
+        })
+    }
+}
+
+impl Options
+{
+    fn leftover(&self) -> Option<&'static str>
+    {
+        if self.user.is_some() {
+            Some("--user")
+        } else if self.role.is_some() {
+            Some("--role")
+        } else if self.all {
+            Some("--all")
+        } else if self.yes {
+            Some("--yes")
+        } else if self.print_config {
+            Some("--print-config")
+        } else {
+            None
+        }
+    }
+}
+
+/// Runs the command given by `raw_args`, which don't include the name of the
+/// binary
+pub async fn run(raw_args: impl IntoIterator<Item = String>) -> Result<(), self::Error>
+{
+    let args = Args::parse(raw_args)?;
+    let load_config = || Config::load(args.config_path.as_deref());
+
+    match args.command {
+        Command::Help => print!("{USAGE}"),
+        Command::Serve => serve::run(load_config()?, args.config_path.clone()).await?,
+        Command::Migrate(command) => command.run(&load_config()?).await?,
+        Command::User(command) => command.run(&load_config()?).await?,
+        Command::Sessions(command) => command.run(&load_config()?).await?,
+        Command::ConfigCheck => {
+            let config = load_config()?;
+            for warning in config.warnings() {
+                eprintln!("warning: {warning}");
+            }
+            print!("{}", config.to_redacted_toml());
+        }
+    }
+
+    Ok(())
+}
+
+/// A single pool to the primary, without the statement timeout the app's own
+/// pools have so long migrations aren't cut short
+async fn connect_postgres(config: &Config) -> Result<PgPool, sqlx::Error>
+{
+    PgPoolOptions::new()
+        .max_connections(config.postgres_max_connections())
+        .acquire_timeout(config.postgres_acquire_timeout())
+        .connect(config.postgres_url())
+        .await
+}
+
+fn session_store(config: &Config) -> Result<session::Store, redis::RedisError>
+{
+    let redis_client = redis::Client::open(config.redis_url())?;
+
+    Ok(session::Store::new(redis_client, config.cookie().clone()))
+}
+
+/// Looks a user up by id or by username, returning their id
+async fn find_user(pg_pool: &PgPool, user: &str) -> Result<Uuid, self::Error>
+{
+    let user_id = match user.parse::<Uuid>() {
+        Ok(user_id) => {
+            sqlx::query_scalar!(r#"select user_id from users where user_id = $1"#, user_id)
+                .fetch_optional(pg_pool)
+                .await?
+        }
+        Err(_not_an_id) => {
+            sqlx::query_scalar!(r#"select user_id from users where username = $1"#, user)
+                .fetch_optional(pg_pool)
+                .await?
+        }
+    };
+
+    user_id.ok_or_else(|| Error::UserNotFound {
+        user: String::from(user),
+    })
+}
+
+/// Reads a password from the first line of stdin, so that it doesn't end up
+/// in the shell's history or the process list
+fn read_password(prompt: &str) -> Result<String, self::Error>
+{
+    let stdin = io::stdin();
+
+    if stdin.is_terminal() {
+        eprint!("{prompt}: ");
+        io::stderr().flush()?;
+    }
+
+    let mut password = String::new();
+    let _read = stdin.lock().read_line(&mut password)?;
+    let password = password.trim_end_matches(['\r', '\n']);
+
+    if password.is_empty() {
+        Err(Error::EmptyPassword)?;
+    }
+
+    Ok(String::from(password))
+}
+
+#[derive(Error)]
+pub enum Error
+{
+    #[error("missing a value for `{arg}`")]
+    MissingArgValue
+    {
+        arg: String,
+    },
+    #[error("missing `{arg}`, see `mindtrails --help`")]
+    MissingArg
+    {
+        arg: &'static str,
+    },
+    #[error("unknown argument `{arg}`, see `mindtrails --help`")]
+    UnknownArg
+    {
+        arg: String,
+    },
+    #[error("unknown command `{command}`, see `mindtrails --help`")]
+    UnknownCommand
+    {
+        command: String,
+    },
+    #[error("`{arg}` doesn't apply to this command, see `mindtrails --help`")]
+    UnusedArg
+    {
+        arg: &'static str,
+    },
+    #[error("`sessions purge` takes exactly one of `--user <user>` and `--all`")]
+    PurgeTarget,
+    #[error("no user `{user}` was found")]
+    UserNotFound
+    {
+        user: String,
+    },
+    #[error("username `{username}` is already taken")]
+    UsernameTaken
+    {
+        username: String,
+    },
+    #[error("no role `{role}` was found")]
+    RoleNotFound
+    {
+        role: String,
+    },
+    #[error("reverting migration {version} drops every account, pass `--yes` to revert it anyway")]
+    RevertBaseline
+    {
+        version: i64,
+    },
+    #[error("the password must not be empty")]
+    EmptyPassword,
+    #[error("{inner}")]
+    Io
+    {
+        #[from]
+        inner: io::Error,
+    },
+    #[error("{inner}")]
+    Config
+    {
+        #[from]
+        inner: config::Error,
+    },
+    #[error("{inner}")]
+    Sqlx
+    {
+        #[from]
+        inner: sqlx::Error,
+    },
+    #[error("{inner}")]
+    Migrate
+    {
+        #[from]
+        inner: sqlx::migrate::MigrateError,
+    },
+    #[error("{inner}")]
+    Redis
+    {
+        #[from]
+        inner: redis::RedisError,
+    },
+    #[error("{inner}")]
+    Session
+    {
+        #[from]
+        inner: session::Error,
+    },
+    #[error("{inner}")]
+    Password
+    {
+        #[from]
+        inner: password::Error,
+    },
+    #[error("{inner}")]
+    Breach
+    {
+        #[from]
+        inner: breach::Error,
+    },
+    #[error("{inner}")]
+    Serve
+    {
+        #[from]
+        inner: http::ServeError,
+    },
+}
+
+// Returning an error from `main` prints it with `Debug`, which would bury
+// messages meant for whoever is running the command (config problems above
+// all) in struct syntax
+impl fmt::Debug for Error
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        fmt::Display::fmt(self, f)
+    }
+}
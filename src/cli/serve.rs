@@ -0,0 +1,50 @@
+use std::path::PathBuf;
+
+use crate::{
+    cli,
+    config::{self, Config},
+    db::DbPools,
+    http,
+    password::breach,
+    telemetry,
+};
+
+/// Migrations aren't applied here, run `mindtrails migrate up` before
+/// starting a build that brings new ones (`/readyz` reports any pending)
+pub(in crate::cli) async fn run(
+    config: Config,
+    config_path: Option<PathBuf>,
+) -> Result<(), cli::Error>
+{
+    let log_filter = telemetry::init(config.log_format(), config.log_filter());
+    for warning in config.warnings() {
+        tracing::warn!("{warning}");
+    }
+
+    let db = DbPools::connect(&config).await?;
+
+    let redis_client = redis::Client::open(config.redis_url())?;
+
+    let breach_checker = match config.breached_passwords_path() {
+        Some(path) => breach::Checker::open(path, config.breached_password_policy()).await?,
+        None => breach::Checker::disabled(),
+    };
+
+    let config = config::Shared::new(config);
+    let _reloader = tokio::spawn(config::reload_on_sighup(
+        config_path,
+        config.clone(),
+        log_filter,
+    ));
+
+    http::serve(
+        config,
+        db,
+        redis_client,
+        breach_checker,
+        http::shutdown_signal(),
+    )
+    .await?;
+
+    Ok(())
+}
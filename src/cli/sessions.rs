@@ -0,0 +1,112 @@
+use sqlx::PgPool;
+
+use uuid::Uuid;
+
+use crate::{
+    audit::{self, Actor, AuditEvent},
+    cli,
+    config::Config,
+};
+
+#[derive(Debug)]
+pub(in crate::cli) enum Command
+{
+    List
+    {
+        user: String,
+    },
+    Purge
+    {
+        /// `None` purges the sessions of every user
+        user: Option<String>,
+    },
+}
+
+impl Command
+{
+    pub(in crate::cli) fn parse(
+        args: &[&str],
+        options: &mut cli::Options,
+    ) -> Result<Self, cli::Error>
+    {
+        match args {
+            ["list"] => match options.user.take() {
+                Some(user) => Ok(Command::List { user }),
+                None => Err(cli::Error::MissingArg {
+                    arg: "--user <user>",
+                }),
+            },
+            ["purge"] => match (options.user.take(), std::mem::take(&mut options.all)) {
+                (Some(user), false) => Ok(Command::Purge { user: Some(user) }),
+                (None, true) => Ok(Command::Purge { user: None }),
+                _ => Err(cli::Error::PurgeTarget),
+            },
+            _ => Err(cli::Error::UnknownCommand {
+                command: format!("sessions {}", args.join(" ")),
+            }),
+        }
+    }
+
+    pub(in crate::cli) async fn run(self, config: &Config) -> Result<(), cli::Error>
+    {
+        let pg_pool = cli::connect_postgres(config).await?;
+        let session_store = cli::session_store(config)?;
+        let audit_log = audit::Log::new(config.audit_key());
+
+        match self {
+            Command::List { user } => {
+                let user_id = cli::find_user(&pg_pool, &user).await?;
+
+                for session in session_store.list_user_sessions(user_id).await? {
+                    match session.expires_in {
+                        Some(expires_in) => {
+                            println!("{}  expires in {}s", session.id, expires_in.as_secs())
+                        }
+                        None => println!("{}  never expires", session.id),
+                    }
+                }
+            }
+            Command::Purge { user: Some(user) } => {
+                let user_id = cli::find_user(&pg_pool, &user).await?;
+
+                let count = session_store.destroy_user_sessions(user_id).await?;
+                record_revoked(&pg_pool, &audit_log, user_id, count).await?;
+
+                println!("destroyed {count} session(s) of user {user_id}");
+            }
+            Command::Purge { user: None } => {
+                let destroyed = session_store.destroy_all_user_sessions().await?;
+
+                let mut total = 0;
+                for (user_id, count) in destroyed {
+                    record_revoked(&pg_pool, &audit_log, user_id, count).await?;
+                    total += count;
+                }
+
+                println!("destroyed {total} session(s)");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+async fn record_revoked(
+    pg_pool: &PgPool,
+    audit_log: &audit::Log,
+    user_id: Uuid,
+    count: usize,
+) -> Result<(), sqlx::Error>
+{
+    audit_log
+        .record(
+            pg_pool,
+            AuditEvent::SessionsRevoked {
+                actor: Actor::Operator,
+                user_id,
+                count,
+            },
+            None,
+        )
+        .await
+}
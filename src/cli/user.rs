@@ -0,0 +1,215 @@
+use crate::{
+    audit::{self, Actor, AuditEvent},
+    cli,
+    config::Config,
+    password,
+};
+
+#[derive(Debug)]
+pub(in crate::cli) enum Command
+{
+    Create
+    {
+        username: String,
+        role: Option<String>,
+    },
+    Disable
+    {
+        user: String,
+    },
+    ResetPassword
+    {
+        user: String,
+    },
+}
+
+impl Command
+{
+    pub(in crate::cli) fn parse(
+        args: &[&str],
+        options: &mut cli::Options,
+    ) -> Result<Self, cli::Error>
+    {
+        match args {
+            ["create", username] => Ok(Command::Create {
+                username: String::from(*username),
+                role: options.role.take(),
+            }),
+            ["disable", user] => Ok(Command::Disable {
+                user: String::from(*user),
+            }),
+            ["reset-password", user] => Ok(Command::ResetPassword {
+                user: String::from(*user),
+            }),
+            _ => Err(cli::Error::UnknownCommand {
+                command: format!("user {}", args.join(" ")),
+            }),
+        }
+    }
+
+    pub(in crate::cli) async fn run(self, config: &Config) -> Result<(), cli::Error>
+    {
+        match self {
+            Command::Create { username, role } => create(config, username, role).await,
+            Command::Disable { user } => disable(config, &user).await,
+            Command::ResetPassword { user } => reset_password(config, &user).await,
+        }
+    }
+}
+
+/// Unlike registering through the API this skips the breached password
+/// check, it's meant for bootstrapping accounts such as the first admin
+async fn create(config: &Config, username: String, role: Option<String>) -> Result<(), cli::Error>
+{
+    let password = cli::read_password("Password")?;
+    let password = password::Hasher::new(config.argon2_params())
+        .hash(password)
+        .await?;
+
+    let pg_pool = cli::connect_postgres(config).await?;
+    let audit_log = audit::Log::new(config.audit_key());
+    let mut transaction = pg_pool.begin().await?;
+
+    let pg_query_res = sqlx::query_scalar!(
+        r#"
+            INSERT INTO "users"(username, password)
+            values ($1, $2)
+            RETURNING user_id
+        "#,
+        username,
+        password
+    )
+    .fetch_one(&mut transaction)
+    .await;
+
+    let user_id = match pg_query_res {
+        Ok(user_id) => user_id,
+        Err(sqlx::Error::Database(database_err))
+            if database_err.constraint() == Some("users_username_key") =>
+        {
+            Err(cli::Error::UsernameTaken { username })?
+        }
+        Err(err) => Err(err)?,
+    };
+
+    audit_log
+        .record(&mut transaction, AuditEvent::UserCreated { user_id }, None)
+        .await?;
+
+    if let Some(role) = role {
+        let role_id = sqlx::query_scalar!(r#"SELECT role_id FROM roles WHERE name = $1"#, role)
+            .fetch_optional(&mut transaction)
+            .await?
+            .ok_or_else(|| cli::Error::RoleNotFound { role: role.clone() })?;
+
+        let _pg_query_res = sqlx::query!(
+            r#"INSERT INTO user_roles(user_id, role_id) values ($1, $2)"#,
+            user_id,
+            role_id
+        )
+        .execute(&mut transaction)
+        .await?;
+
+        audit_log
+            .record(
+                &mut transaction,
+                AuditEvent::RoleGranted {
+                    actor: Actor::Operator,
+                    user_id,
+                    role,
+                },
+                None,
+            )
+            .await?;
+    }
+
+    transaction.commit().await?;
+
+    println!("created user {user_id}");
+
+    Ok(())
+}
+
+async fn disable(config: &Config, user: &str) -> Result<(), cli::Error>
+{
+    let pg_pool = cli::connect_postgres(config).await?;
+    let session_store = cli::session_store(config)?;
+    let audit_log = audit::Log::new(config.audit_key());
+
+    let user_id = cli::find_user(&pg_pool, user).await?;
+
+    let mut transaction = pg_pool.begin().await?;
+
+    let _pg_query_res = sqlx::query!(
+        r#"UPDATE "users" SET disabled = true WHERE user_id = $1"#,
+        user_id
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    audit_log
+        .record(
+            &mut transaction,
+            AuditEvent::AccountDisabled {
+                actor: Actor::Operator,
+                user_id,
+            },
+            None,
+        )
+        .await?;
+    transaction.commit().await?;
+
+    let destroyed = session_store.destroy_user_sessions(user_id).await?;
+
+    println!("disabled user {user_id} and destroyed {destroyed} session(s)");
+
+    Ok(())
+}
+
+async fn reset_password(config: &Config, user: &str) -> Result<(), cli::Error>
+{
+    let pg_pool = cli::connect_postgres(config).await?;
+    let session_store = cli::session_store(config)?;
+    let audit_log = audit::Log::new(config.audit_key());
+
+    let user_id = cli::find_user(&pg_pool, user).await?;
+
+    let password = cli::read_password("Temporary password")?;
+    let password = password::Hasher::new(config.argon2_params())
+        .hash(password)
+        .await?;
+
+    let mut transaction = pg_pool.begin().await?;
+
+    let _pg_query_res = sqlx::query!(
+        r#"
+            UPDATE "users" SET password = $2, password_reset_required = true
+            WHERE user_id = $1
+        "#,
+        user_id,
+        password
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    audit_log
+        .record(
+            &mut transaction,
+            AuditEvent::PasswordResetForced {
+                actor: Actor::Operator,
+                user_id,
+            },
+            None,
+        )
+        .await?;
+    transaction.commit().await?;
+
+    let destroyed = session_store.destroy_user_sessions(user_id).await?;
+
+    println!(
+        "set a temporary password for user {user_id}, which has to be changed on the next \
+         login, and destroyed {destroyed} session(s)"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use std::str::FromStr;
+
+use sqlx::{
+    postgres::{PgConnectOptions, PgPoolOptions},
+    PgPool,
+};
+
+use crate::config::Config;
+
+/// Connections to the primary database and, if one is configured, a read
+/// replica.
+///
+/// Anything that writes, or can't tolerate replication lag (logins and
+/// permission checks above all), goes through `writer`. Reads that can be a
+/// little stale go through `reader`, which falls back to the primary when
+/// there's no replica or the handle was pinned to the primary.
+#[derive(Debug, Clone)]
+pub struct DbPools
+{
+    writer: PgPool,
+    reader: Option<PgPool>,
+    pinned: bool,
+}
+
+impl DbPools
+{
+    pub async fn connect(config: &Config) -> Result<Self, sqlx::Error>
+    {
+        let writer = connect(config, config.postgres_url()).await?;
+        let reader = match config.postgres_replica_url() {
+            Some(replica_url) => Some(connect(config, replica_url).await?),
+            None => None,
+        };
+
+        Ok(DbPools {
+            writer,
+            reader,
+            pinned: false,
+        })
+    }
+
+    pub fn writer(&self) -> &PgPool
+    {
+        &self.writer
+    }
+
+    pub fn reader(&self) -> &PgPool
+    {
+        match &self.reader {
+            Some(reader) if !self.pinned => reader,
+            _ => &self.writer,
+        }
+    }
+
+    /// The replica regardless of pinning, for health checks and metrics
+    pub fn replica(&self) -> Option<&PgPool>
+    {
+        self.reader.as_ref()
+    }
+
+    /// Waits for the connections in use to be given back and closes every
+    /// connection, to the replica too
+    pub async fn close(&self)
+    {
+        self.writer.close().await;
+        if let Some(reader) = &self.reader {
+            reader.close().await;
+        }
+    }
+
+    /// A handle whose reads go to the primary too, for reads that need to see
+    /// writes made just before
+    pub fn pinned_to_primary(&self) -> Self
+    {
+        DbPools {
+            pinned: true,
+            ..self.clone()
+        }
+    }
+}
+
+async fn connect(config: &Config, url: &str) -> Result<PgPool, sqlx::Error>
+{
+    let mut connect_options = PgConnectOptions::from_str(url)?;
+    if let Some(statement_timeout) = config.postgres_statement_timeout() {
+        connect_options = connect_options.options([(
+            "statement_timeout",
+            format!("{}ms", statement_timeout.as_millis()),
+        )]);
+    }
+
+    PgPoolOptions::new()
+        .max_connections(config.postgres_max_connections())
+        .min_connections(config.postgres_min_connections())
+        .acquire_timeout(config.postgres_acquire_timeout())
+        .idle_timeout(config.postgres_idle_timeout())
+        .max_lifetime(config.postgres_max_lifetime())
+        .connect_with(connect_options)
+        .await
+}
@@ -0,0 +1,429 @@
+use axum::{
+    middleware,
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use sqlx::PgPool;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{
+    audit::{self, Actor, AuditEvent},
+    db::DbPools,
+    http::{
+        self,
+        authz::{self, permission, RequirePermission},
+        client_info::ClientInfo,
+        path, query, session,
+    },
+};
+
+pub(in crate::http) const FALLBACK_PER_PAGE: i64 = 50;
+pub(in crate::http) const MAX_PER_PAGE: i64 = 200;
+
+pub(in crate::http) fn router() -> Router
+{
+    Router::new()
+        .route("/admin/users", get(list_users))
+        .route("/admin/users/:user_id", get(fetch_user))
+        .route("/admin/users/:user_id/disable", post(disable_user))
+        .route("/admin/users/:user_id/enable", post(enable_user))
+        .route(
+            "/admin/users/:user_id/password-reset",
+            post(force_password_reset),
+        )
+        .route(
+            "/admin/users/:user_id/sessions/revoke",
+            post(revoke_user_sessions),
+        )
+        .route(
+            "/admin/users/:user_id/roles/:role",
+            post(grant_role).delete(revoke_role),
+        )
+        .route_layer(middleware::from_extractor::<
+            RequirePermission<permission::AdminAccess>,
+        >())
+}
+
+#[derive(Serialize)]
+struct User
+{
+    user_id: Uuid,
+    username: String,
+    disabled: bool,
+    password_reset_required: bool,
+}
+
+#[derive(Deserialize)]
+struct ListUsers
+{
+    search: Option<String>,
+    page: Option<i64>,
+    per_page: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct UserPage
+{
+    users: Vec<User>,
+    page: i64,
+    per_page: i64,
+    total: i64,
+}
+
+async fn list_users(
+    _permission: RequirePermission<permission::UsersRead>,
+    db: Extension<DbPools>,
+    query::extractor::Query(req): query::extractor::Query<ListUsers>,
+) -> Result<Json<UserPage>, http::Error>
+{
+    let ListUsers {
+        search,
+        page,
+        per_page,
+    } = req;
+
+    let page = page.unwrap_or(1).max(1);
+    let per_page = per_page.unwrap_or(FALLBACK_PER_PAGE).clamp(1, MAX_PER_PAGE);
+    let offset = query::page_offset(page, per_page)?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+            SELECT count(*) AS "total!" FROM users
+            WHERE $1::text IS NULL OR strpos(lower(username), lower($1)) > 0
+        "#,
+        search
+    )
+    .fetch_one(db.reader())
+    .await?;
+
+    let users = sqlx::query_as!(
+        User,
+        r#"
+            SELECT user_id, username, disabled, password_reset_required FROM users
+            WHERE $1::text IS NULL OR strpos(lower(username), lower($1)) > 0
+            ORDER BY username
+            LIMIT $2 OFFSET $3
+        "#,
+        search,
+        per_page,
+        offset
+    )
+    .fetch_all(db.reader())
+    .await?;
+
+    Ok(Json(UserPage {
+        users,
+        page,
+        per_page,
+        total,
+    }))
+}
+
+#[derive(Serialize)]
+struct UserDetails
+{
+    #[serde(flatten)]
+    user: User,
+    roles: Vec<String>,
+    permissions: Vec<String>,
+}
+
+async fn fetch_user(
+    _permission: RequirePermission<permission::UsersRead>,
+    db: Extension<DbPools>,
+    path::extractor::Path(user_id): path::extractor::Path<Uuid>,
+) -> Result<Json<UserDetails>, http::Error>
+{
+    let user = sqlx::query_as!(
+        User,
+        r#"
+            SELECT user_id, username, disabled, password_reset_required FROM users
+            WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_optional(db.reader())
+    .await?
+    .ok_or(Error::UserNotFound)?;
+
+    let grants = authz::Grants::load(db.reader(), user_id)
+        .await?
+        .ok_or(Error::UserNotFound)?;
+
+    Ok(Json(UserDetails {
+        user,
+        roles: grants.roles,
+        permissions: grants.permissions,
+    }))
+}
+
+async fn disable_user(
+    permission: RequirePermission<permission::UsersWrite>,
+    db: Extension<DbPools>,
+    audit_log: Extension<audit::Log>,
+    client: ClientInfo,
+    session_store: Extension<session::Store>,
+    path::extractor::Path(user_id): path::extractor::Path<Uuid>,
+) -> Result<http::StatusCode, http::Error>
+{
+    let event = AuditEvent::AccountDisabled {
+        actor: Actor::User(permission.user_id),
+        user_id,
+    };
+    set_disabled(db.writer(), &audit_log, user_id, true, event, &client).await?;
+
+    // A disabled account can't log in, so it shouldn't stay logged in either
+    let _destroyed = session_store.destroy_user_sessions(user_id).await?;
+
+    Ok(http::StatusCode::NO_CONTENT)
+}
+
+async fn enable_user(
+    permission: RequirePermission<permission::UsersWrite>,
+    db: Extension<DbPools>,
+    audit_log: Extension<audit::Log>,
+    client: ClientInfo,
+    path::extractor::Path(user_id): path::extractor::Path<Uuid>,
+) -> Result<http::StatusCode, http::Error>
+{
+    let event = AuditEvent::AccountEnabled {
+        actor: Actor::User(permission.user_id),
+        user_id,
+    };
+    set_disabled(db.writer(), &audit_log, user_id, false, event, &client).await?;
+
+    Ok(http::StatusCode::NO_CONTENT)
+}
+
+async fn set_disabled(
+    pg_pool: &PgPool,
+    audit_log: &audit::Log,
+    user_id: Uuid,
+    disabled: bool,
+    event: AuditEvent,
+    client: &ClientInfo,
+) -> Result<(), http::Error>
+{
+    let mut transaction = pg_pool.begin().await?;
+
+    let pg_query_res = sqlx::query!(
+        r#"UPDATE "users" SET disabled = $2 WHERE user_id = $1"#,
+        user_id,
+        disabled
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    if pg_query_res.rows_affected() == 0 {
+        Err(Error::UserNotFound)?;
+    }
+
+    audit_log
+        .record(&mut transaction, event, Some(&client.audit()))
+        .await?;
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+async fn force_password_reset(
+    permission: RequirePermission<permission::UsersWrite>,
+    db: Extension<DbPools>,
+    audit_log: Extension<audit::Log>,
+    client: ClientInfo,
+    session_store: Extension<session::Store>,
+    path::extractor::Path(user_id): path::extractor::Path<Uuid>,
+) -> Result<http::StatusCode, http::Error>
+{
+    let mut transaction = db.writer().begin().await?;
+
+    let pg_query_res = sqlx::query!(
+        r#"UPDATE "users" SET password_reset_required = true WHERE user_id = $1"#,
+        user_id
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    if pg_query_res.rows_affected() == 0 {
+        Err(Error::UserNotFound)?;
+    }
+
+    audit_log
+        .record(
+            &mut transaction,
+            AuditEvent::PasswordResetForced {
+                actor: Actor::User(permission.user_id),
+                user_id,
+            },
+            Some(&client.audit()),
+        )
+        .await?;
+    transaction.commit().await?;
+
+    let _destroyed = session_store.destroy_user_sessions(user_id).await?;
+
+    Ok(http::StatusCode::NO_CONTENT)
+}
+
+#[derive(Serialize)]
+struct RevokedSessions
+{
+    revoked: usize,
+}
+
+async fn revoke_user_sessions(
+    permission: RequirePermission<permission::UsersWrite>,
+    db: Extension<DbPools>,
+    audit_log: Extension<audit::Log>,
+    client: ClientInfo,
+    session_store: Extension<session::Store>,
+    path::extractor::Path(user_id): path::extractor::Path<Uuid>,
+) -> Result<Json<RevokedSessions>, http::Error>
+{
+    let revoked = session_store.destroy_user_sessions(user_id).await?;
+
+    audit_log
+        .record(
+            db.writer(),
+            AuditEvent::SessionsRevoked {
+                actor: Actor::User(permission.user_id),
+                user_id,
+                count: revoked,
+            },
+            Some(&client.audit()),
+        )
+        .await?;
+
+    Ok(Json(RevokedSessions { revoked }))
+}
+
+async fn grant_role(
+    permission: RequirePermission<permission::UsersWrite>,
+    db: Extension<DbPools>,
+    audit_log: Extension<audit::Log>,
+    client: ClientInfo,
+    path::extractor::Path((user_id, role)): path::extractor::Path<(Uuid, String)>,
+) -> Result<http::StatusCode, http::Error>
+{
+    let mut transaction = db.writer().begin().await?;
+
+    let role_id = sqlx::query_scalar!(r#"SELECT role_id FROM roles WHERE name = $1"#, role)
+        .fetch_optional(&mut transaction)
+        .await?
+        .ok_or(Error::RoleNotFound)?;
+
+    let pg_query_res = sqlx::query!(
+        r#"
+            INSERT INTO user_roles(user_id, role_id)
+            values ($1, $2)
+            ON CONFLICT DO NOTHING
+        "#,
+        user_id,
+        role_id
+    )
+    .execute(&mut transaction)
+    .await;
+
+    match pg_query_res {
+        // Already holding the role isn't worth an audit entry
+        Ok(pg_query_res) if pg_query_res.rows_affected() == 0 => {
+            return Ok(http::StatusCode::NO_CONTENT)
+        }
+        Ok(_) => {}
+        Err(sqlx::Error::Database(database_err))
+            if database_err.constraint() == Some("user_roles_user_id_fkey") =>
+        {
+            Err(Error::UserNotFound)?
+        }
+        Err(err) => Err(err)?,
+    }
+
+    audit_log
+        .record(
+            &mut transaction,
+            AuditEvent::RoleGranted {
+                actor: Actor::User(permission.user_id),
+                user_id,
+                role,
+            },
+            Some(&client.audit()),
+        )
+        .await?;
+    transaction.commit().await?;
+
+    Ok(http::StatusCode::NO_CONTENT)
+}
+
+async fn revoke_role(
+    permission: RequirePermission<permission::UsersWrite>,
+    db: Extension<DbPools>,
+    audit_log: Extension<audit::Log>,
+    client: ClientInfo,
+    path::extractor::Path((user_id, role)): path::extractor::Path<(Uuid, String)>,
+) -> Result<http::StatusCode, http::Error>
+{
+    let mut transaction = db.writer().begin().await?;
+
+    let role_id = sqlx::query_scalar!(r#"SELECT role_id FROM roles WHERE name = $1"#, role)
+        .fetch_optional(&mut transaction)
+        .await?
+        .ok_or(Error::RoleNotFound)?;
+
+    let pg_query_res = sqlx::query!(
+        r#"DELETE FROM user_roles WHERE user_id = $1 AND role_id = $2"#,
+        user_id,
+        role_id
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    if pg_query_res.rows_affected() == 0 {
+        return Ok(http::StatusCode::NO_CONTENT);
+    }
+
+    audit_log
+        .record(
+            &mut transaction,
+            AuditEvent::RoleRevoked {
+                actor: Actor::User(permission.user_id),
+                user_id,
+                role,
+            },
+            Some(&client.audit()),
+        )
+        .await?;
+    transaction.commit().await?;
+
+    Ok(http::StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Error)]
+enum Error
+{
+    #[error("no user with the provided id was found")]
+    UserNotFound,
+    #[error("no role with the provided name was found")]
+    RoleNotFound,
+}
+
+impl From<Error> for http::Error
+{
+    fn from(err: Error) -> Self
+    {
+        let error_code = match err {
+            Error::UserNotFound => http::error::Code::USER_NOT_FOUND,
+            Error::RoleNotFound => http::error::Code::ROLE_NOT_FOUND,
+        };
+
+        let message = err.to_string();
+
+        http::Error {
+            error_code,
+            message,
+            errors: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use axum::{middleware, routing::get, Extension, Json, Router};
+
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    audit::{self, Verification},
+    db::DbPools,
+    http::{
+        self, admin,
+        authz::{permission, RequirePermission},
+        query,
+    },
+};
+
+pub(in crate::http) fn router() -> Router
+{
+    Router::new()
+        .route("/admin/audit", get(list_entries))
+        .route("/admin/audit/verify", get(verify_chain))
+        .route_layer(middleware::from_extractor::<
+            RequirePermission<permission::AdminAccess>,
+        >())
+}
+
+#[derive(Serialize)]
+struct Entry
+{
+    audit_id: i64,
+    #[serde(with = "time::serde::rfc3339")]
+    occurred_at: OffsetDateTime,
+    action: String,
+    actor_id: Option<Uuid>,
+    target_id: Option<Uuid>,
+    details: Option<serde_json::Value>,
+    hash: String,
+}
+
+#[derive(Deserialize)]
+struct ListEntries
+{
+    actor_id: Option<Uuid>,
+    target_id: Option<Uuid>,
+    action: Option<String>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    since: Option<OffsetDateTime>,
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    until: Option<OffsetDateTime>,
+    page: Option<i64>,
+    per_page: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct EntryPage
+{
+    entries: Vec<Entry>,
+    page: i64,
+    per_page: i64,
+    total: i64,
+}
+
+/// Lists audit entries newest first, `since` is inclusive and `until` is not
+async fn list_entries(
+    _permission: RequirePermission<permission::AuditRead>,
+    db: Extension<DbPools>,
+    query::extractor::Query(req): query::extractor::Query<ListEntries>,
+) -> Result<Json<EntryPage>, http::Error>
+{
+    let ListEntries {
+        actor_id,
+        target_id,
+        action,
+        since,
+        until,
+        page,
+        per_page,
+    } = req;
+
+    let page = page.unwrap_or(1).max(1);
+    let per_page = per_page
+        .unwrap_or(admin::FALLBACK_PER_PAGE)
+        .clamp(1, admin::MAX_PER_PAGE);
+    let offset = query::page_offset(page, per_page)?;
+
+    let total = sqlx::query_scalar!(
+        r#"
+            SELECT count(*) AS "total!" FROM audit_log
+            WHERE ($1::uuid IS NULL OR actor_id = $1)
+                AND ($2::uuid IS NULL OR target_id = $2)
+                AND ($3::text IS NULL OR action = $3)
+                AND ($4::timestamptz IS NULL OR occurred_at >= $4)
+                AND ($5::timestamptz IS NULL OR occurred_at < $5)
+        "#,
+        actor_id,
+        target_id,
+        action,
+        since,
+        until
+    )
+    .fetch_one(db.reader())
+    .await?;
+
+    let entries = sqlx::query_as!(
+        Entry,
+        r#"
+            SELECT
+                audit_id,
+                occurred_at,
+                action,
+                actor_id,
+                target_id,
+                details,
+                encode(hash, 'hex') AS "hash!"
+            FROM audit_log
+            WHERE ($1::uuid IS NULL OR actor_id = $1)
+                AND ($2::uuid IS NULL OR target_id = $2)
+                AND ($3::text IS NULL OR action = $3)
+                AND ($4::timestamptz IS NULL OR occurred_at >= $4)
+                AND ($5::timestamptz IS NULL OR occurred_at < $5)
+            ORDER BY audit_id DESC
+            LIMIT $6 OFFSET $7
+        "#,
+        actor_id,
+        target_id,
+        action,
+        since,
+        until,
+        per_page,
+        offset
+    )
+    .fetch_all(db.reader())
+    .await?;
+
+    Ok(Json(EntryPage {
+        entries,
+        page,
+        per_page,
+        total,
+    }))
+}
+
+#[derive(Serialize)]
+struct ChainStatus
+{
+    intact: bool,
+    entries: Option<u64>,
+    broken_at: Option<i64>,
+}
+
+async fn verify_chain(
+    _permission: RequirePermission<permission::AuditRead>,
+    db: Extension<DbPools>,
+    audit_log: Extension<audit::Log>,
+) -> Result<Json<ChainStatus>, http::Error>
+{
+    let status = match audit_log.verify(db.reader()).await? {
+        Verification::Intact { entries } => ChainStatus {
+            intact: true,
+            entries: Some(entries),
+            broken_at: None,
+        },
+        Verification::Broken { audit_id } => ChainStatus {
+            intact: false,
+            entries: None,
+            broken_at: Some(audit_id),
+        },
+    };
+
+    Ok(Json(status))
+}
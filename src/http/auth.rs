@@ -1,86 +1,218 @@
-use axum::{routing::get, Extension, Router};
-use sqlx::PgPool;
+use axum::{extract::DefaultBodyLimit, routing::get, Extension, Router};
 
 use serde::Deserialize;
 use thiserror::Error;
+use utoipa::{OpenApi, ToSchema};
+use uuid::Uuid;
 
 use crate::{
+    audit::{self, AuditEvent},
+    config,
+    db::DbPools,
     http::{
-        self, json,
+        self, authz,
+        client_info::ClientInfo,
+        error::Code,
+        json, locale, rate_limit,
         session::{self, Session},
     },
     password,
 };
 
-pub(in crate::http) fn router() -> Router
+pub(in crate::http) fn router(limits: &config::Limits) -> Router
 {
-    Router::new().route(
-        "/auth",
-        get(fetch_auth_session)
-            .post(create_auth_session)
-            .delete(delete_auth_session),
-    )
+    Router::new()
+        .route(
+            "/auth",
+            get(fetch_auth_session)
+                .post(create_auth_session)
+                .delete(delete_auth_session),
+        )
+        // Anyone can log in, so there's no need to read more than credentials
+        .route_layer(DefaultBodyLimit::max(limits.credentials_body()))
 }
 
-async fn fetch_auth_session(user_id: session::extractor::UserId) -> Result<String, http::Error>
+#[derive(OpenApi)]
+#[openapi(
+    paths(fetch_auth_session, create_auth_session, delete_auth_session),
+    components(schemas(CreateAuthSession))
+)]
+pub(in crate::http) struct Api;
+
+/// What each operation of `Api` can fail with, besides the codes any
+/// operation can fail with
+pub(in crate::http) const ERROR_CODES: &[(&str, &[Code])] = &[
+    (
+        "fetch_auth_session",
+        &[Code::NO_SESSION_FOUND, Code::MUST_BE_AUTHENTICATED],
+    ),
+    (
+        "create_auth_session",
+        &[
+            Code::JSON_SYNTAX_ERROR,
+            Code::JSON_DATA_ERROR,
+            Code::JSON_MISSING_CONTENT_TYPE,
+            Code::PAYLOAD_TOO_LARGE,
+            Code::CROSS_SITE_REQUEST,
+            Code::USER_NOT_FOUND,
+            Code::WRONG_PASSWORD,
+            Code::ACCOUNT_DISABLED,
+            Code::PASSWORD_RESET_REQUIRED,
+            Code::TOO_MANY_LOGIN_ATTEMPTS,
+        ],
+    ),
+    (
+        "delete_auth_session",
+        &[
+            Code::NO_SESSION_FOUND,
+            Code::MUST_BE_AUTHENTICATED,
+            Code::FORBIDDEN_WHILE_IMPERSONATING,
+            Code::CROSS_SITE_REQUEST,
+        ],
+    ),
+];
+
+/// The id of the logged in user
+#[utoipa::path(
+    get,
+    path = "/auth",
+    tag = "auth",
+    responses((
+        status = 200,
+        description = "The user's id",
+        body = String,
+        content_type = "text/plain",
+        headers((
+            "x-impersonator-id" = Uuid,
+            description = "The admin's id, if they are impersonating the user"
+        ))
+    ))
+)]
+async fn fetch_auth_session(
+    user_id: session::extractor::UserId,
+) -> Result<(http::HeaderMap, String), http::Error>
 {
     match user_id {
-        session::extractor::UserId::Found(user_id) => Ok(user_id.to_string()),
+        session::extractor::UserId::Found(user_id) => {
+            Ok((http::HeaderMap::new(), user_id.to_string()))
+        }
+        session::extractor::UserId::Impersonated {
+            user_id,
+            impersonator_id,
+        } => {
+            // Lets the frontend make it obvious that an admin is seeing the
+            // app as someone else
+            let mut headers = http::HeaderMap::new();
+            let header_value = http::HeaderValue::from_str(&impersonator_id.to_string())
+                // SAFETY: The hyphenated representation of a UUID is always
+                // ASCII-only
+                .unwrap();
+            let _prev_value = headers.insert(http::IMPERSONATOR_ID_HEADER, header_value);
+
+            Ok((headers, user_id.to_string()))
+        }
         session::extractor::UserId::NotFound => Err(Error::MustBeAuthenticated)?,
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct CreateAuthSession
 {
     username: String,
     password: String,
 }
 
+/// Logs in, opening a session
+#[utoipa::path(
+    post,
+    path = "/auth",
+    tag = "auth",
+    request_body = CreateAuthSession,
+    responses((
+        status = 204,
+        description = "Logged in",
+        headers(("set-cookie" = String, description = "The session cookie"))
+    ))
+)]
 async fn create_auth_session(
-    pg_pool: Extension<PgPool>,
+    db: Extension<DbPools>,
+    audit_log: Extension<audit::Log>,
+    client: ClientInfo,
     session_store: Extension<session::Store>,
+    login_limiter: Extension<rate_limit::LoginLimiter>,
     json::extractor::Json(req): json::extractor::Json<CreateAuthSession>,
 ) -> Result<(http::HeaderMap, http::StatusCode), http::Error>
 {
     let CreateAuthSession { username, password } = req;
 
+    login_limiter.attempt(&username, &client).await?;
+
     let user = sqlx::query!(
-        r#"select user_id, password from users where username = $1"#,
+        r#"
+            select user_id, password, disabled, password_reset_required, locale from users
+            where username = $1
+        "#,
         username
     )
-    .fetch_optional(&*pg_pool)
+    .fetch_optional(db.writer())
     .await?;
 
     match user {
         Some(user) => {
             let password_is_correct = password::verify(password, user.password).await?;
-
             if password_is_correct {
+                login_limiter.reset(&username, &client).await?;
+            }
+
+            // Account status is only disclosed to someone who knows the
+            // password
+            if password_is_correct && user.disabled {
+                Err(Error::AccountDisabled)?
+            } else if password_is_correct && user.password_reset_required {
+                Err(Error::PasswordResetRequired)?
+            } else if password_is_correct {
+                audit_log
+                    .record(
+                        db.writer(),
+                        AuditEvent::LoginSucceeded {
+                            user_id: user.user_id,
+                        },
+                        Some(&client.audit()),
+                    )
+                    .await?;
+
+                let grants = authz::Grants::load(db.writer(), user.user_id)
+                    .await?
+                    .ok_or(Error::UserNotFound)?;
+
                 let mut session = Session::new();
                 session.insert("user_id", user.user_id).await?;
+                session.insert(authz::GRANTS_SESSION_KEY, grants).await?;
+                if let Some(locale) = user.locale.as_deref().and_then(locale::Locale::from_tag) {
+                    locale::prefer(locale);
+                    session.insert(locale::LOCALE_SESSION_KEY, locale).await?;
+                }
                 // SAFETY: This cannot fail as store_session propagates `None`
                 // upon a `None` field for the session's cookie value, which
                 // will never be empty as we create the session above and never
                 // mutate its cookie value
                 let cookie = session_store.store_session(session).await?.unwrap();
 
-                let mut headers = http::HeaderMap::new();
-                let header_value = http::HeaderValue::from_str(&format!(
-                    "{}={}; SameSite=None; Secure",
-                    session::SESSION_COOKIE_NAME,
-                    cookie
+                Ok((
+                    session_store.set_cookie_headers(&cookie),
+                    http::StatusCode::NO_CONTENT,
                 ))
-                // SAFETY: It is known in advance that `SESSION_COOKIE_NAME` as
-                // well as the cookie propagated from the init of the session
-                // are both always going to be ASCII-only, therefore the
-                // formatted string will be ASCII-only, so creating the
-                // HeaderValue will never fail
-                .unwrap();
-                let _prev_value = headers.insert(http::header::SET_COOKIE, header_value);
-
-                Ok((headers, http::StatusCode::NO_CONTENT))
             } else {
+                audit_log
+                    .record(
+                        db.writer(),
+                        AuditEvent::LoginFailed {
+                            user_id: user.user_id,
+                        },
+                        Some(&client.audit()),
+                    )
+                    .await?;
+
                 Err(Error::WrongPassword)?
             }
         }
@@ -88,28 +220,53 @@ async fn create_auth_session(
     }
 }
 
+/// Logs out, destroying the session
+#[utoipa::path(
+    delete,
+    path = "/auth",
+    tag = "auth",
+    responses((
+        status = 204,
+        description = "Logged out",
+        headers(("set-cookie" = String, description = "Expires the session cookie"))
+    ))
+)]
 async fn delete_auth_session(
+    db: Extension<DbPools>,
+    audit_log: Extension<audit::Log>,
+    client: ClientInfo,
     session_store: Extension<session::Store>,
     session: session::extractor::Session,
 ) -> Result<(http::HeaderMap, http::StatusCode), http::Error>
 {
     match session {
         session::extractor::Session::Found(session) => {
-            let cookie = session.clone().into_cookie_value();
+            // Impersonation has its own way out which gets audited
+            if session
+                .get::<Uuid>(session::IMPERSONATOR_ID_SESSION_KEY)
+                .await
+                .is_some()
+            {
+                Err(Error::ForbiddenWhileImpersonating)?;
+            }
 
+            let user_id = session.get::<Uuid>("user_id").await;
             session_store.destroy_session(session).await?;
 
-            let mut headers = http::HeaderMap::new();
-            let header_value = http::HeaderValue::from_str(&format!(
-                "{}={:?}; SameSite=None; Secure; Max-Age=0;",
-                session::SESSION_COOKIE_NAME,
-                cookie
-            ))
-            // SAAFETY: See relevant safety note for create_auth_session
-            .unwrap();
-            let _prev_value = headers.insert(http::header::SET_COOKIE, header_value);
+            if let Some(user_id) = user_id {
+                audit_log
+                    .record(
+                        db.writer(),
+                        AuditEvent::LoggedOut { user_id },
+                        Some(&client.audit()),
+                    )
+                    .await?;
+            }
 
-            Ok((headers, http::StatusCode::NO_CONTENT))
+            Ok((
+                session_store.clear_cookie_headers(),
+                http::StatusCode::NO_CONTENT,
+            ))
         }
         session::extractor::Session::NotFound => Err(Error::MustBeAuthenticated)?,
     }
@@ -118,12 +275,18 @@ async fn delete_auth_session(
 #[derive(Debug, Error)]
 enum Error
 {
-    #[error("no user with the provided was found")]
+    #[error("no user with the provided username was found")]
     UserNotFound,
     #[error("the provided password is wrong")]
     WrongPassword,
     #[error("must be authenticated")]
     MustBeAuthenticated,
+    #[error("this account has been disabled")]
+    AccountDisabled,
+    #[error("the password must be changed before logging in")]
+    PasswordResetRequired,
+    #[error("not allowed while impersonating another user")]
+    ForbiddenWhileImpersonating,
 }
 
 impl From<Error> for http::Error
@@ -134,20 +297,17 @@ impl From<Error> for http::Error
             Error::UserNotFound => http::error::Code::USER_NOT_FOUND,
             Error::WrongPassword => http::error::Code::WRONG_PASSWORD,
             Error::MustBeAuthenticated => http::error::Code::MUST_BE_AUTHENTICATED,
-        };
-
-        let status_code = match err {
-            Error::UserNotFound => http::StatusCode::NOT_FOUND,
-            Error::WrongPassword => http::StatusCode::UNPROCESSABLE_ENTITY,
-            Error::MustBeAuthenticated => http::StatusCode::UNAUTHORIZED,
+            Error::AccountDisabled => http::error::Code::ACCOUNT_DISABLED,
+            Error::PasswordResetRequired => http::error::Code::PASSWORD_RESET_REQUIRED,
+            Error::ForbiddenWhileImpersonating => http::error::Code::FORBIDDEN_WHILE_IMPERSONATING,
         };
 
         let message = err.to_string();
 
         http::Error {
             error_code,
-            status_code,
             message,
+            errors: Vec::new(),
         }
     }
 }
@@ -0,0 +1,253 @@
+use std::marker::PhantomData;
+
+use axum::{extract::FromRequestParts, http::request, Extension, RequestPartsExt};
+use sqlx::PgPool;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{
+    db::DbPools,
+    http::{self, session},
+};
+
+pub(in crate::http) const GRANTS_SESSION_KEY: &str = "grants";
+/// The role whose holders operate the app, and so can't be impersonated
+pub(in crate::http) const ADMIN_ROLE: &str = "admin";
+
+pub(in crate::http) trait Permission
+{
+    const NAME: &'static str;
+    /// Whether actions requiring this permission are refused while an admin
+    /// is impersonating someone
+    const DESTRUCTIVE: bool;
+}
+
+macro_rules! permission {
+    ($name:ident, $permission:literal) => {
+        permission!($name, $permission, false);
+    };
+    ($name:ident, $permission:literal, destructive) => {
+        permission!($name, $permission, true);
+    };
+    ($name:ident, $permission:literal, $destructive:literal) => {
+        #[derive(Debug)]
+        pub(in crate::http) struct $name;
+
+        impl super::Permission for $name
+        {
+            const NAME: &'static str = $permission;
+            const DESTRUCTIVE: bool = $destructive;
+        }
+    };
+}
+
+pub(in crate::http) mod permission
+{
+    permission!(UsersRead, "users:read");
+    permission!(UsersWrite, "users:write", destructive);
+    permission!(UsersImpersonate, "users:impersonate", destructive);
+    permission!(AdminAccess, "admin:access");
+    permission!(AuditRead, "audit:read");
+}
+
+/// The roles of a user and the permissions they add up to, as of
+/// `users.authz_version` being `version`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(in crate::http) struct Grants
+{
+    version: i64,
+    pub(in crate::http) roles: Vec<String>,
+    pub(in crate::http) permissions: Vec<String>,
+}
+
+impl Grants
+{
+    pub(in crate::http) async fn load(
+        pg_pool: &PgPool,
+        user_id: Uuid,
+    ) -> Result<Option<Self>, sqlx::Error>
+    {
+        let version = sqlx::query_scalar!(
+            r#"select authz_version from users where user_id = $1"#,
+            user_id
+        )
+        .fetch_optional(pg_pool)
+        .await?;
+
+        let version = match version {
+            Some(version) => version,
+            None => return Ok(None),
+        };
+
+        let roles = sqlx::query_scalar!(
+            r#"
+                SELECT roles.name FROM user_roles
+                JOIN roles USING (role_id)
+                WHERE user_roles.user_id = $1
+                ORDER BY roles.name
+            "#,
+            user_id
+        )
+        .fetch_all(pg_pool)
+        .await?;
+
+        let permissions = sqlx::query_scalar!(
+            r#"
+                SELECT DISTINCT permissions.name FROM user_roles
+                JOIN role_permissions USING (role_id)
+                JOIN permissions USING (permission_id)
+                WHERE user_roles.user_id = $1
+                ORDER BY permissions.name
+            "#,
+            user_id
+        )
+        .fetch_all(pg_pool)
+        .await?;
+
+        Ok(Some(Grants {
+            version,
+            roles,
+            permissions,
+        }))
+    }
+
+    pub(in crate::http) fn has_permission(&self, permission: &str) -> bool
+    {
+        self.permissions.iter().any(|granted| granted == permission)
+    }
+}
+
+/// Rejects the request unless the session's user currently holds the
+/// permission `P`.
+///
+/// The grants cached in the session are checked against
+/// `users.authz_version` on every request and reloaded when stale, so role
+/// changes apply to existing sessions without having to log in again.
+#[derive(Debug)]
+pub(in crate::http) struct RequirePermission<P>
+{
+    pub(in crate::http) user_id: Uuid,
+    _permission: PhantomData<P>,
+}
+
+#[async_trait]
+impl<S, P> FromRequestParts<S> for RequirePermission<P>
+where
+    S: Send + Sync,
+    P: Permission,
+{
+    type Rejection = http::Error;
+
+    async fn from_request_parts(
+        parts: &mut request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection>
+    {
+        let store = parts
+            .extract::<Extension<session::Store>>()
+            .await
+            .map_err(|_| session::Error::MissingStoreExtension)?;
+        let db = parts
+            .extract::<Extension<DbPools>>()
+            .await
+            .map_err(|_| Error::MissingDbPoolsExtension)?;
+
+        let mut session = match parts.extract::<session::extractor::Session>().await? {
+            session::extractor::Session::Found(session) => session,
+            session::extractor::Session::NotFound => Err(Error::MustBeAuthenticated)?,
+        };
+        let user_id = match session.get::<Uuid>("user_id").await {
+            Some(user_id) => user_id,
+            None => Err(Error::MustBeAuthenticated)?,
+        };
+
+        if P::DESTRUCTIVE
+            && session
+                .get::<Uuid>(session::IMPERSONATOR_ID_SESSION_KEY)
+                .await
+                .is_some()
+        {
+            Err(Error::ForbiddenWhileImpersonating)?;
+        }
+
+        // Never from the replica, a revoked permission has to stop working
+        // right away
+        let current_version = sqlx::query_scalar!(
+            r#"select authz_version from users where user_id = $1"#,
+            user_id
+        )
+        .fetch_optional(db.writer())
+        .await?
+        .ok_or(Error::MustBeAuthenticated)?;
+
+        let grants = match session.get::<Grants>(GRANTS_SESSION_KEY).await {
+            Some(grants) if grants.version == current_version => grants,
+            _ => {
+                let grants = Grants::load(db.writer(), user_id)
+                    .await?
+                    .ok_or(Error::MustBeAuthenticated)?;
+                session.insert(GRANTS_SESSION_KEY, &grants).await?;
+                // The session may have been revoked since it was loaded
+                if !store.update_session(&session).await? {
+                    Err(Error::MustBeAuthenticated)?;
+                }
+
+                grants
+            }
+        };
+
+        if grants.has_permission(P::NAME) {
+            Ok(RequirePermission {
+                user_id,
+                _permission: PhantomData,
+            })
+        } else {
+            Err(Error::Forbidden)?
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+enum Error
+{
+    #[error("missing request database pools extension")]
+    MissingDbPoolsExtension,
+    #[error("must be authenticated")]
+    MustBeAuthenticated,
+    #[error("missing the permission required for this action")]
+    Forbidden,
+    #[error("not allowed while impersonating another user")]
+    ForbiddenWhileImpersonating,
+}
+
+impl From<Error> for http::Error
+{
+    fn from(err: Error) -> Self
+    {
+        let error_code = match err {
+            Error::MissingDbPoolsExtension => http::error::Code::INTERNAL_SERVER_ERROR,
+            Error::MustBeAuthenticated => http::error::Code::MUST_BE_AUTHENTICATED,
+            Error::Forbidden => http::error::Code::FORBIDDEN,
+            Error::ForbiddenWhileImpersonating => http::error::Code::FORBIDDEN_WHILE_IMPERSONATING,
+        };
+
+        let message = match err {
+            Error::MissingDbPoolsExtension => {
+                tracing::error!(error = %err, "authorization error");
+                String::from(http::error::INTERNAL_SERVER_ERROR_MESSAGE)
+            }
+            Error::MustBeAuthenticated | Error::Forbidden | Error::ForbiddenWhileImpersonating => {
+                err.to_string()
+            }
+        };
+
+        http::Error {
+            error_code,
+            message,
+            errors: Vec::new(),
+        }
+    }
+}
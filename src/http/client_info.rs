@@ -0,0 +1,316 @@
+use std::net::{IpAddr, SocketAddr};
+
+use async_trait::async_trait;
+use axum::{
+    extract::{ConnectInfo, FromRequestParts, State},
+    http::{header::HeaderName, request, Request},
+    middleware::Next,
+    response,
+};
+use thiserror::Error;
+
+use crate::{
+    audit,
+    config::{self, IpNetwork},
+    http::{self, header, HeaderMap},
+};
+
+const FLY_CLIENT_IP_HEADER: HeaderName = HeaderName::from_static("fly-client-ip");
+const X_FORWARDED_FOR_HEADER: HeaderName = HeaderName::from_static("x-forwarded-for");
+const X_FORWARDED_PROTO_HEADER: HeaderName = HeaderName::from_static("x-forwarded-proto");
+
+/// Who sent the request, as told by the proxies in `proxies.trusted` when the
+/// request came through them and by the connection itself otherwise
+#[derive(Debug, Clone)]
+pub(in crate::http) struct ClientInfo
+{
+    /// `None` when not known, as with connections that aren't over TCP or a
+    /// proxy reporting the client as `unknown`
+    pub(in crate::http) ip: Option<IpAddr>,
+    pub(in crate::http) scheme: Scheme,
+    pub(in crate::http) user_agent: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(in crate::http) enum Scheme
+{
+    Http,
+    Https,
+}
+
+/// A step on the way from the client to a proxy, as the proxy reports it
+#[derive(Debug, Clone, Copy)]
+struct Hop
+{
+    ip: Option<IpAddr>,
+    scheme: Option<Scheme>,
+}
+
+impl ClientInfo
+{
+    /// What the audit log keeps of the client
+    pub(in crate::http) fn audit(&self) -> audit::Client
+    {
+        audit::Client {
+            ip: self.ip,
+            user_agent: self.user_agent.clone(),
+        }
+    }
+}
+
+impl Scheme
+{
+    fn from_proto(proto: &str) -> Option<Scheme>
+    {
+        if proto.eq_ignore_ascii_case("http") {
+            Some(Scheme::Http)
+        } else if proto.eq_ignore_ascii_case("https") {
+            Some(Scheme::Https)
+        } else {
+            None
+        }
+    }
+}
+
+/// Works out the `ClientInfo` of every request once, before anything else
+/// looks at the request
+pub(in crate::http) async fn resolve<B>(
+    State(config): State<config::Shared>,
+    mut req: Request<B>,
+    next: Next<B>,
+) -> response::Response
+{
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_canonical());
+    let client = Hop {
+        ip: peer,
+        scheme: None,
+    };
+
+    let client = {
+        let config = config.load();
+        match peer {
+            Some(peer) if is_trusted(config.trusted_proxies(), peer) => forwarded_client(
+                config.trusted_proxies(),
+                config.behind_fly_proxy(),
+                peer,
+                req.headers(),
+            ),
+            _ => client,
+        }
+    };
+    // Only set by the listener serving over TLS
+    let connection_scheme = req
+        .extensions()
+        .get::<Scheme>()
+        .copied()
+        .unwrap_or(Scheme::Http);
+
+    let client_info = ClientInfo {
+        ip: client.ip,
+        scheme: client.scheme.unwrap_or(connection_scheme),
+        user_agent: req
+            .headers()
+            .get(header::USER_AGENT)
+            .and_then(|user_agent| user_agent.to_str().ok())
+            .map(String::from),
+    };
+
+    let _prev_value = req.extensions_mut().insert(client_info);
+
+    next.run(req).await
+}
+
+fn is_trusted(trusted_proxies: &[IpNetwork], ip: IpAddr) -> bool
+{
+    trusted_proxies.iter().any(|network| network.contains(ip))
+}
+
+/// The client as reported by the proxies, walking back from `peer` for as
+/// long as the hop it reports was a trusted proxy as well. Anything before
+/// the first untrusted hop could have been made up by the client.
+///
+/// `Fly-Client-IP` is only believed when `fly_proxy` says the proxies are
+/// Fly's, others would pass it on as the client sent it.
+fn forwarded_client(
+    trusted_proxies: &[IpNetwork],
+    fly_proxy: bool,
+    peer: IpAddr,
+    headers: &HeaderMap,
+) -> Hop
+{
+    let forwarded_proto =
+        last_value(headers, &X_FORWARDED_PROTO_HEADER).and_then(Scheme::from_proto);
+
+    // Fly's proxy replaces whatever the client sent in this one
+    let fly_client_ip = last_value(headers, &FLY_CLIENT_IP_HEADER)
+        .filter(|_ip| fly_proxy)
+        .and_then(|ip| ip.parse().ok());
+    if let Some(ip) = fly_client_ip {
+        return Hop {
+            ip: Some(ip),
+            scheme: forwarded_proto,
+        };
+    }
+
+    let hops = match forwarded(headers) {
+        Some(hops) => hops,
+        None => x_forwarded_for(headers, forwarded_proto).unwrap_or_default(),
+    };
+
+    let mut client = Hop {
+        ip: Some(peer),
+        scheme: forwarded_proto,
+    };
+    for hop in hops.into_iter().rev() {
+        match client.ip {
+            Some(ip) if is_trusted(trusted_proxies, ip) => client = hop,
+            _ => break,
+        }
+    }
+
+    client
+}
+
+/// The hops in `Forwarded` (RFC 7239), the client first
+fn forwarded(headers: &HeaderMap) -> Option<Vec<Hop>>
+{
+    let values = joined_values(headers, &header::FORWARDED)?;
+
+    let hops = values
+        .split(',')
+        .map(|element| {
+            let mut hop = Hop {
+                ip: None,
+                scheme: None,
+            };
+            for pair in element.split(';') {
+                let (key, value) = match pair.split_once('=') {
+                    Some((key, value)) => (key.trim(), value.trim().trim_matches('"')),
+                    None => continue,
+                };
+
+                if key.eq_ignore_ascii_case("for") {
+                    hop.ip = node_ip(value);
+                } else if key.eq_ignore_ascii_case("proto") {
+                    hop.scheme = Scheme::from_proto(value);
+                }
+            }
+
+            hop
+        })
+        .collect();
+
+    Some(hops)
+}
+
+/// The hops in `X-Forwarded-For`, the client first, all of which are taken
+/// to have been reached over `scheme`
+fn x_forwarded_for(headers: &HeaderMap, scheme: Option<Scheme>) -> Option<Vec<Hop>>
+{
+    let values = joined_values(headers, &X_FORWARDED_FOR_HEADER)?;
+
+    let hops = values
+        .split(',')
+        .map(|ip| Hop {
+            ip: node_ip(ip.trim()),
+            scheme,
+        })
+        .collect();
+
+    Some(hops)
+}
+
+/// The address of a node as written in `Forwarded`, which might come with a
+/// port and has IPv6 addresses in brackets, or a plain address. `unknown` and
+/// obfuscated identifiers have none.
+fn node_ip(node: &str) -> Option<IpAddr>
+{
+    if let Some(rest) = node.strip_prefix('[') {
+        let (ip, _port) = rest.split_once(']')?;
+        return ip.parse::<IpAddr>().ok().map(|ip| ip.to_canonical());
+    }
+
+    let ip = match node.parse::<IpAddr>() {
+        Ok(ip) => ip,
+        // Only an IPv4 address has a single `:`, before its port
+        Err(_) => node.split_once(':')?.0.parse().ok()?,
+    };
+
+    Some(ip.to_canonical())
+}
+
+/// Every value of a header that can be sent more than once, as a single list
+fn joined_values(headers: &HeaderMap, name: &HeaderName) -> Option<String>
+{
+    let values = headers
+        .get_all(name)
+        .iter()
+        .map(|value| value.to_str().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.join(","))
+    }
+}
+
+/// The last item of a header's list, the one added by the closest proxy
+fn last_value<'a>(headers: &'a HeaderMap, name: &HeaderName) -> Option<&'a str>
+{
+    let value = headers.get_all(name).iter().next_back()?.to_str().ok()?;
+    value.rsplit(',').next().map(str::trim)
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientInfo
+where
+    S: Send + Sync,
+{
+    type Rejection = http::Error;
+
+    async fn from_request_parts(
+        parts: &mut request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection>
+    {
+        parts
+            .extensions
+            .get::<ClientInfo>()
+            .cloned()
+            .ok_or_else(|| Error::MissingClientInfoExtension.into())
+    }
+}
+
+#[derive(Debug, Error)]
+enum Error
+{
+    #[error("missing request client info extension")]
+    MissingClientInfoExtension,
+}
+
+impl From<Error> for http::Error
+{
+    fn from(err: Error) -> Self
+    {
+        let error_code = match err {
+            Error::MissingClientInfoExtension => http::error::Code::INTERNAL_SERVER_ERROR,
+        };
+
+        let message = match err {
+            Error::MissingClientInfoExtension => {
+                tracing::error!(error = %err, "client info error");
+                String::from(http::error::INTERNAL_SERVER_ERROR_MESSAGE)
+            }
+        };
+
+        http::Error {
+            error_code,
+            message,
+            errors: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use axum::http::request::Parts;
+use tower_http::cors::{self, AllowOrigin, CorsLayer};
+
+use crate::{
+    config::{self, Config},
+    http::{self, header, request_id, users, version},
+};
+
+/// Only the allowed origins follow config reloads, the other settings are
+/// read once when the layer is built
+pub(in crate::http) fn layer(config: config::Shared) -> CorsLayer
+{
+    let startup_config = config.load();
+
+    CorsLayer::new()
+        .allow_methods(startup_config.cors_methods().to_vec())
+        .allow_credentials(true)
+        .allow_origin(AllowOrigin::predicate(move |origin, parts| {
+            is_allowed(&config, origin, parts)
+        }))
+        .allow_headers([header::CONTENT_TYPE])
+        .expose_headers([
+            header::HeaderName::from_static(http::IMPERSONATOR_ID_HEADER),
+            request_id::REQUEST_ID_HEADER,
+            version::DEPRECATION_HEADER,
+            version::SUNSET_HEADER,
+            users::PASSWORD_BREACHED_HEADER,
+            header::LINK,
+        ])
+        .max_age(startup_config.cors_max_age())
+        // The layer replaces whatever `Vary` the response already had, so it
+        // has to list what error responses are negotiated on as well
+        .vary(
+            cors::preflight_request_headers()
+                .chain([header::ACCEPT, header::ACCEPT_LANGUAGE])
+                .collect::<Vec<_>>(),
+        )
+}
+
+fn is_allowed(config: &config::Shared, origin: &http::HeaderValue, parts: &Parts) -> bool
+{
+    let allowed = match origin.to_str() {
+        Ok(origin) => allows_origin(&config.load(), origin),
+        Err(_non_ascii) => false,
+    };
+
+    // The browser only reports a generic CORS failure to the frontend, so this
+    // is the only place to find out which origin was missing from the list
+    if !allowed {
+        tracing::info!(?origin, method = %parts.method, uri = %parts.uri, "rejected CORS origin");
+    }
+
+    allowed
+}
+
+/// Whether `origin` is one of `cors.origins`, the only ones browsers may send
+/// requests that change anything from as well
+pub(in crate::http) fn allows_origin(config: &Config, origin: &str) -> bool
+{
+    config
+        .cors_origins()
+        .iter()
+        .any(|pattern| pattern.matches(origin))
+}
@@ -0,0 +1,115 @@
+use axum::{
+    http::{Request, Uri},
+    middleware::Next,
+    response::{self, IntoResponse},
+    Extension,
+};
+use thiserror::Error;
+
+use crate::{
+    config,
+    http::{self, cors, header, HeaderMap},
+};
+
+/// Where a request was sent from, as far as its headers tell
+#[derive(Debug)]
+enum Source
+{
+    /// Neither `Origin` nor `Referer` was sent, as by clients other than
+    /// browsers
+    Unknown,
+    Origin(String),
+    /// `Origin: null`, as sent from sandboxed frames and `data:` URLs, or
+    /// headers that can't be read
+    Opaque,
+}
+
+/// Refuses requests that change anything when a browser sent them from an
+/// origin outside of `cors.origins`.
+///
+/// CORS alone doesn't cover this, as a cross-site form post is sent without
+/// asking first, along with the `SameSite=None` session cookie. Requests
+/// without `Origin` or `Referer` are let through, browsers send one of them
+/// with every such request and other clients have no cookie of someone else's
+/// to ride on.
+pub(in crate::http) async fn check_origin<B>(
+    config: Extension<config::Shared>,
+    req: Request<B>,
+    next: Next<B>,
+) -> response::Response
+{
+    if req.method().is_safe() {
+        return next.run(req).await;
+    }
+
+    let allowed = match source(req.headers()) {
+        Source::Unknown => true,
+        Source::Origin(origin) => cors::allows_origin(&config.load(), &origin),
+        Source::Opaque => false,
+    };
+
+    if allowed {
+        next.run(req).await
+    } else {
+        tracing::info!(
+            origin = ?req.headers().get(header::ORIGIN),
+            referer = ?req.headers().get(header::REFERER),
+            method = %req.method(),
+            uri = %req.uri(),
+            "rejected cross-site request"
+        );
+
+        http::Error::from(Error::CrossSiteRequest).into_response()
+    }
+}
+
+/// `Origin` if there is one, otherwise the origin of the page in `Referer`
+fn source(headers: &HeaderMap) -> Source
+{
+    if let Some(origin) = headers.get(header::ORIGIN) {
+        return match origin.to_str() {
+            Ok("null") | Err(_) => Source::Opaque,
+            Ok(origin) => Source::Origin(String::from(origin)),
+        };
+    }
+
+    let referer = match headers.get(header::REFERER) {
+        Some(referer) => referer,
+        None => return Source::Unknown,
+    };
+    let uri = match referer.to_str().map(str::parse::<Uri>) {
+        Ok(Ok(uri)) => uri,
+        _ => return Source::Opaque,
+    };
+
+    match (uri.scheme_str(), uri.host(), uri.port_u16()) {
+        (Some(scheme), Some(host), Some(port)) => {
+            Source::Origin(format!("{scheme}://{host}:{port}"))
+        }
+        (Some(scheme), Some(host), None) => Source::Origin(format!("{scheme}://{host}")),
+        _ => Source::Opaque,
+    }
+}
+
+#[derive(Debug, Error)]
+enum Error
+{
+    #[error("requests that change anything are not allowed from this origin")]
+    CrossSiteRequest,
+}
+
+impl From<Error> for http::Error
+{
+    fn from(err: Error) -> Self
+    {
+        let error_code = match err {
+            Error::CrossSiteRequest => http::error::Code::CROSS_SITE_REQUEST,
+        };
+
+        http::Error {
+            error_code,
+            message: err.to_string(),
+            errors: Vec::new(),
+        }
+    }
+}
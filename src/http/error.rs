@@ -1,88 +1,446 @@
 use std::num::NonZeroU16;
 
-use axum::{response, Json};
+use axum::{
+    http::{header, Request},
+    middleware::Next,
+    response,
+    routing::get,
+    Json, Router,
+};
 
-use serde::Serialize;
-use serde_json::json;
+use serde::{Serialize, Serializer};
+use utoipa::ToSchema;
 
 use crate::{
-    http::{self, session},
-    password,
+    http::{self, locale::Locale, path, session},
+    metrics, password,
 };
 
+pub(in crate::http) const PROBLEM_JSON: &str = "application/problem+json";
+
+tokio::task_local! {
+    static WANTS_PROBLEM_JSON: bool;
+}
+
 #[derive(Debug)]
 pub(in crate::http) struct Error
 {
     pub(in crate::http) error_code: http::error::Code,
-    pub(in crate::http) status_code: http::StatusCode,
     pub(in crate::http) message: String,
+    /// What is wrong with which fields of the request, if the error comes
+    /// down to specific ones
+    pub(in crate::http) errors: Vec<FieldError>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(in crate::http) struct FieldError
+{
+    /// Path to the field within the request body, e.g. `settings.locale` or
+    /// `answers[2]`
+    pub(in crate::http) field: String,
+    pub(in crate::http) message: String,
+}
+
+/// The original shape of error responses
+#[derive(Serialize, ToSchema)]
+pub(in crate::http) struct ErrorPayload
+{
+    message: String,
+    #[schema(value_type = u16)]
+    code: Code,
+    request_id: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<FieldError>,
+}
+
+/// RFC 7807 problem details, with our own code as an extension member
+#[derive(Serialize, ToSchema)]
+pub(in crate::http) struct ProblemPayload
+{
+    /// Resolves to the error code's entry in the catalog at `/errors`
+    #[serde(rename = "type")]
+    problem_type: String,
+    title: &'static str,
+    status: u16,
+    detail: String,
+    /// The id of the request, as in `x-request-id`
+    instance: Option<String>,
+    #[schema(value_type = u16)]
+    code: Code,
+    errors: Vec<FieldError>,
 }
 
 impl response::IntoResponse for Error
 {
     fn into_response(self) -> response::Response
     {
-        let payload = json!({
-            "message": self.message,
-            "code": self.error_code
-        });
+        metrics::HTTP_ERRORS
+            .with_label_values(&[&self.error_code.code.to_string()])
+            .inc();
 
-        (self.status_code, Json(payload)).into_response()
+        let request_id = http::request_id::current();
+        // Falls back to the English message of the error itself when the
+        // code has no translation
+        let (locale, message) = match http::locale::current() {
+            locale @ Locale::En => (locale, self.message),
+            locale => match locale.message(self.error_code) {
+                Some(message) => (locale, String::from(message)),
+                None => (Locale::En, self.message),
+            },
+        };
+        let wants_problem_json = WANTS_PROBLEM_JSON.try_with(|wants| *wants).unwrap_or(false);
+
+        let mut res = if wants_problem_json {
+            let payload = ProblemPayload {
+                problem_type: format!("/errors/{}", self.error_code.code),
+                title: self.error_code.title,
+                status: self.error_code.status.as_u16(),
+                detail: message,
+                instance: request_id,
+                code: self.error_code,
+                errors: self.errors,
+            };
+
+            let mut res = (self.error_code.status, Json(payload)).into_response();
+            let _prev_value = res.headers_mut().insert(
+                header::CONTENT_TYPE,
+                http::HeaderValue::from_static(PROBLEM_JSON),
+            );
+
+            res
+        } else {
+            let payload = ErrorPayload {
+                message,
+                code: self.error_code,
+                request_id,
+                errors: self.errors,
+            };
+
+            (self.error_code.status, Json(payload)).into_response()
+        };
+
+        let _prev_value = res.headers_mut().insert(
+            header::CONTENT_LANGUAGE,
+            http::HeaderValue::from_static(locale.tag()),
+        );
+
+        res
     }
 }
 
-#[derive(Debug, Serialize)]
-pub(super) struct Code(NonZeroU16);
+/// Renders errors as `application/problem+json` for the clients asking for
+/// it through `Accept`, others keep getting the original `{message, code}`
+/// shape
+pub(in crate::http) async fn negotiate<B>(req: Request<B>, next: Next<B>) -> response::Response
+{
+    let wants_problem_json = req
+        .headers()
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|accept| accept.to_str().ok())
+        .flat_map(|accept| accept.split(','))
+        .any(|media_range| accepts(media_range, PROBLEM_JSON));
 
-macro_rules! code {
-    ($name:ident, $code:expr) => {
-        pub(super) const $name: Code =
-            Code(unsafe { ::std::num::NonZeroU16::new_unchecked($code) });
-    };
+    WANTS_PROBLEM_JSON
+        .scope(wants_problem_json, next.run(req))
+        .await
+}
+
+/// Whether a single media range from an `Accept` header names `media_type`
+/// without ruling it out with `q=0`
+fn accepts(media_range: &str, media_type: &str) -> bool
+{
+    let mut params = media_range.split(';').map(str::trim);
+
+    let matches = params
+        .next()
+        .is_some_and(|range| range.eq_ignore_ascii_case(media_type));
+    let refused = params.any(|param| {
+        param
+            .strip_prefix("q=")
+            .and_then(|q| q.parse::<f32>().ok())
+            .is_some_and(|q| q == 0.0)
+    });
+
+    matches && !refused
+}
+
+/// An error code along with everything clients need to know about it, see
+/// `CODES` for the whole registry
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Code
+{
+    code: NonZeroU16,
+    status: http::StatusCode,
+    category: Category,
+    title: &'static str,
+    /// In English, used when nothing more specific is known
+    message: &'static str,
+}
+
+/// What an error code relates to, which sets the hundreds digit of the code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum Category
+{
+    Json = 1,
+    Session = 2,
+    Url = 3,
+    Auth = 4,
+    Users = 5,
+    Admin = 6,
+    Internal = 9,
 }
 
-pub(super) const INTERNAL_SERVER_ERROR_MESSAGE: &str = "Internal Server Error";
-
-// 1xx - JSON
-//     100 - JSON Syntax Error
-//     110 - JSON Data Error
-//     120 - JSON Missing Content Type
-// 2xx - Session
-//     201 - No Session Found
-// 4xx - Auth
-//     401 - User Not Found
-//     402 - Wrong Password
-//     403 - Must Be Authenticated
-// 5xx - Users
-//     501 - Username Taken
-// 999 - Internal Server Error
 impl Code
 {
-    #![allow(unsafe_code)]
+    const fn new(
+        code: u16,
+        status: http::StatusCode,
+        category: Category,
+        title: &'static str,
+        message: &'static str,
+    ) -> Self
+    {
+        let code = match NonZeroU16::new(code) {
+            Some(code) => code,
+            None => panic!("error codes start at 1"),
+        };
 
-    code!(JSON_SYNTAX_ERROR, 100);
-    code!(JSON_DATA_ERROR, 110);
-    code!(JSON_MISSING_CONTENT_TYPE, 120);
+        Code {
+            code,
+            status,
+            category,
+            title,
+            message,
+        }
+    }
 
-    code!(NO_SESSION_FOUND, 201);
+    pub(super) fn code(&self) -> u16
+    {
+        self.code.get()
+    }
 
-    code!(USER_NOT_FOUND, 401);
-    code!(WRONG_PASSWORD, 402);
-    code!(MUST_BE_AUTHENTICATED, 403);
+    pub(super) fn status(&self) -> http::StatusCode
+    {
+        self.status
+    }
 
-    code!(USERNAME_TAKEN, 501);
+    pub(super) fn category(&self) -> Category
+    {
+        self.category
+    }
+
+    pub(super) fn title(&self) -> &'static str
+    {
+        self.title
+    }
 
-    code!(INTERNAL_SERVER_ERROR, 999);
+    pub(super) fn message(&self) -> &'static str
+    {
+        self.message
+    }
+}
+
+impl Serialize for Code
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_u16(self.code.get())
+    }
+}
+
+/// Declares every error code once, as a `Code` constant and an entry in
+/// `CODES`
+macro_rules! codes {
+    ($(
+        $category:ident => {
+            $($name:ident = $code:literal, $status:ident, $title:literal, $message:literal;)*
+        }
+    )*) => {
+        impl Code
+        {
+            $($(
+                pub(super) const $name: Code = Code::new(
+                    $code,
+                    http::StatusCode::$status,
+                    Category::$category,
+                    $title,
+                    $message,
+                );
+            )*)*
+        }
+
+        /// Every error code the API can respond with, in ascending order
+        pub(super) const CODES: &[Code] = &[$($(Code::$name),*),*];
+    };
+}
+
+codes! {
+    Json => {
+        JSON_SYNTAX_ERROR = 100, BAD_REQUEST, "JSON Syntax Error",
+            "the request body is not valid JSON";
+        JSON_DATA_ERROR = 110, UNPROCESSABLE_ENTITY, "JSON Data Error",
+            "the request body does not have the expected fields";
+        JSON_MISSING_CONTENT_TYPE = 120, UNSUPPORTED_MEDIA_TYPE, "JSON Missing Content Type",
+            "expected a request with `Content-Type: application/json`";
+        PAYLOAD_TOO_LARGE = 130, PAYLOAD_TOO_LARGE, "Payload Too Large",
+            "the request body is too large";
+    }
+    Session => {
+        NO_SESSION_FOUND = 201, BAD_REQUEST, "No Session Found",
+            "no session found";
+    }
+    Url => {
+        INVALID_PATH_PARAMETER = 301, BAD_REQUEST, "Invalid Path Parameter",
+            "a path parameter is invalid";
+        INVALID_QUERY_PARAMETER = 302, BAD_REQUEST, "Invalid Query Parameter",
+            "a query parameter is invalid";
+        API_VERSION_GONE = 303, GONE, "API Version Gone",
+            "this version of the API is no longer served";
+    }
+    Auth => {
+        USER_NOT_FOUND = 401, NOT_FOUND, "User Not Found",
+            "no such user was found";
+        WRONG_PASSWORD = 402, UNPROCESSABLE_ENTITY, "Wrong Password",
+            "the provided password is wrong";
+        MUST_BE_AUTHENTICATED = 403, UNAUTHORIZED, "Must Be Authenticated",
+            "must be authenticated";
+        FORBIDDEN = 404, FORBIDDEN, "Forbidden",
+            "missing the permission required for this action";
+        ACCOUNT_DISABLED = 405, FORBIDDEN, "Account Disabled",
+            "this account has been disabled";
+        PASSWORD_RESET_REQUIRED = 406, FORBIDDEN, "Password Reset Required",
+            "the password must be changed before logging in";
+        FORBIDDEN_WHILE_IMPERSONATING = 407, FORBIDDEN, "Forbidden While Impersonating",
+            "not allowed while impersonating another user";
+        NOT_IMPERSONATING = 408, BAD_REQUEST, "Not Impersonating",
+            "not currently impersonating another user";
+        CANNOT_IMPERSONATE_SELF = 409, UNPROCESSABLE_ENTITY, "Cannot Impersonate Self",
+            "cannot impersonate yourself";
+        TOO_MANY_LOGIN_ATTEMPTS = 410, TOO_MANY_REQUESTS, "Too Many Login Attempts",
+            "too many failed login attempts, try again later";
+        CROSS_SITE_REQUEST = 411, FORBIDDEN, "Cross-Site Request",
+            "requests that change anything are not allowed from this origin";
+        CANNOT_IMPERSONATE_DISABLED = 412, UNPROCESSABLE_ENTITY, "Cannot Impersonate Disabled",
+            "cannot impersonate a disabled account";
+        CANNOT_IMPERSONATE_ADMIN = 413, FORBIDDEN, "Cannot Impersonate Admin",
+            "cannot impersonate another admin";
+    }
+    Users => {
+        USERNAME_TAKEN = 501, CONFLICT, "Username Taken",
+            "username already taken";
+        PASSWORD_BREACHED = 502, UNPROCESSABLE_ENTITY, "Password Breached",
+            "password appears in a known data breach";
+        REGISTRATION_DISABLED = 503, FORBIDDEN, "Registration Disabled",
+            "registration of new users is disabled";
+    }
+    Admin => {
+        ROLE_NOT_FOUND = 601, NOT_FOUND, "Role Not Found",
+            "no role with the provided name was found";
+    }
+    Internal => {
+        REQUEST_TIMED_OUT = 901, SERVICE_UNAVAILABLE, "Request Timed Out",
+            "the request took too long to handle";
+        OVERLOADED = 902, SERVICE_UNAVAILABLE, "Overloaded",
+            "the server is handling too many requests, try again later";
+        SHUTTING_DOWN = 903, SERVICE_UNAVAILABLE, "Shutting Down",
+            "the server shut down before the request could be handled, try again";
+        INTERNAL_SERVER_ERROR = 999, INTERNAL_SERVER_ERROR, "Internal Server Error",
+            "Internal Server Error";
+    }
+}
+
+// Checked while compiling, so no code can be declared twice or outside of
+// the range of its category
+const _: () = {
+    let mut i = 0;
+    while i < CODES.len() {
+        let code = CODES[i].code.get();
+
+        if code / 100 != CODES[i].category as u16 {
+            panic!("error code outside of the range of its category");
+        }
+        if i > 0 && CODES[i - 1].code.get() >= code {
+            panic!("error codes must be declared once each, in ascending order");
+        }
+
+        i += 1;
+    }
+};
+
+pub(super) const INTERNAL_SERVER_ERROR_MESSAGE: &str = Code::INTERNAL_SERVER_ERROR.message;
+
+/// Publishes the registry, so clients can generate their error handling from
+/// it rather than keep a copy in sync by hand
+pub(in crate::http) fn router() -> Router
+{
+    Router::new()
+        .route("/errors", get(list_codes))
+        .route("/errors/:code", get(fetch_code))
+}
+
+#[derive(Serialize)]
+struct CodeList
+{
+    codes: Vec<CodeEntry>,
+}
+
+#[derive(Serialize)]
+struct CodeEntry
+{
+    code: u16,
+    status: u16,
+    category: Category,
+    title: &'static str,
+    message: &'static str,
+}
+
+impl From<&Code> for CodeEntry
+{
+    fn from(code: &Code) -> Self
+    {
+        CodeEntry {
+            code: code.code(),
+            status: code.status().as_u16(),
+            category: code.category(),
+            title: code.title(),
+            message: code.message(),
+        }
+    }
+}
+
+async fn list_codes() -> Json<CodeList>
+{
+    Json(CodeList {
+        codes: CODES.iter().map(CodeEntry::from).collect(),
+    })
+}
+
+/// What the `type` of a problem+json error points to
+async fn fetch_code(
+    path::extractor::Path(code): path::extractor::Path<u16>,
+) -> Result<Json<CodeEntry>, http::StatusCode>
+{
+    CODES
+        .iter()
+        .find(|registered| registered.code() == code)
+        .map(|registered| Json(CodeEntry::from(registered)))
+        .ok_or(http::StatusCode::NOT_FOUND)
 }
 
 impl From<sqlx::Error> for Error
 {
-    fn from(_sqlx_err: sqlx::Error) -> Self
+    fn from(sqlx_err: sqlx::Error) -> Self
     {
+        tracing::error!(error = %sqlx_err, ?sqlx_err, "database error");
+
         Error {
             error_code: Code::INTERNAL_SERVER_ERROR,
-            status_code: http::StatusCode::INTERNAL_SERVER_ERROR,
             message: String::from(INTERNAL_SERVER_ERROR_MESSAGE),
+            errors: Vec::new(),
         }
     }
 }
@@ -91,6 +449,10 @@ impl From<session::Error> for Error
 {
     fn from(session_err: session::Error) -> Self
     {
+        if !matches!(session_err, session::Error::NoSessionFound) {
+            tracing::error!(error = %session_err, ?session_err, "session error");
+        }
+
         let error_code = match session_err {
             session::Error::Base64Decode { .. }
             | session::Error::SerdeJson { .. }
@@ -99,14 +461,6 @@ impl From<session::Error> for Error
             session::Error::NoSessionFound { .. } => Code::NO_SESSION_FOUND,
         };
 
-        let status_code = match session_err {
-            session::Error::Base64Decode { .. }
-            | session::Error::SerdeJson { .. }
-            | session::Error::Redis { .. }
-            | session::Error::MissingStoreExtension => http::StatusCode::INTERNAL_SERVER_ERROR,
-            session::Error::NoSessionFound { .. } => http::StatusCode::BAD_REQUEST,
-        };
-
         let message = match session_err {
             session::Error::Base64Decode { .. }
             | session::Error::SerdeJson { .. }
@@ -117,20 +471,22 @@ impl From<session::Error> for Error
 
         Error {
             error_code,
-            status_code,
             message,
+            errors: Vec::new(),
         }
     }
 }
 
 impl From<password::Error> for Error
 {
-    fn from(_password_err: password::Error) -> Self
+    fn from(password_err: password::Error) -> Self
     {
+        tracing::error!(error = %password_err, ?password_err, "password error");
+
         Error {
             error_code: Code::INTERNAL_SERVER_ERROR,
-            status_code: http::StatusCode::INTERNAL_SERVER_ERROR,
             message: String::from(INTERNAL_SERVER_ERROR_MESSAGE),
+            errors: Vec::new(),
         }
     }
 }
@@ -0,0 +1,195 @@
+use std::{future::Future, time::Duration};
+
+use axum::{routing::get, Extension, Json, Router};
+use sqlx::{migrate::Migrate, Connection, PgPool};
+use tokio::time;
+
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::{
+    db::DbPools,
+    http::{self, session, Shutdown},
+};
+
+// Each dependency gets this long to respond before it's reported as down,
+// comfortably below the timeout of the fly.io check polling `/readyz`
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub(in crate::http) fn router() -> Router
+{
+    Router::new()
+        .route("/healthz", get(liveness))
+        .route("/readyz", get(readiness))
+}
+
+/// Only says the process is up and serving requests, dependencies being down
+/// isn't something a restart would fix
+async fn liveness() -> http::StatusCode
+{
+    http::StatusCode::NO_CONTENT
+}
+
+#[derive(Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+enum Check
+{
+    Ok,
+    Failed
+    {
+        reason: String,
+    },
+}
+
+#[derive(Serialize)]
+struct Readiness
+{
+    ready: bool,
+    /// Once set the app is about to stop, whatever the checks say
+    shutting_down: bool,
+    postgres: Check,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    postgres_replica: Option<Check>,
+    session_store: Check,
+    migrations: Check,
+}
+
+/// Fails as soon as the app starts shutting down, so load balancers stop
+/// sending it requests before it stops accepting them
+async fn readiness(
+    db: Extension<DbPools>,
+    session_store: Extension<session::Store>,
+    shutdown: Extension<Shutdown>,
+) -> (http::StatusCode, Json<Readiness>)
+{
+    let (postgres, postgres_replica, session_store, migrations) = tokio::join!(
+        check(check_postgres(db.writer())),
+        async {
+            match db.replica() {
+                Some(replica) => Some(check(check_postgres(replica)).await),
+                None => None,
+            }
+        },
+        check(session_store.ping()),
+        check(check_migrations(db.writer())),
+    );
+
+    let shutting_down = shutdown.is_draining();
+    let ready = !shutting_down
+        && [&postgres, &session_store, &migrations]
+            .into_iter()
+            .chain(&postgres_replica)
+            .all(|check| matches!(check, Check::Ok));
+    let status_code = if ready {
+        http::StatusCode::OK
+    } else {
+        http::StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status_code,
+        Json(Readiness {
+            ready,
+            shutting_down,
+            postgres,
+            postgres_replica,
+            session_store,
+            migrations,
+        }),
+    )
+}
+
+async fn check<F, E>(future: F) -> Check
+where
+    F: Future<Output = Result<(), E>>,
+    E: Into<Error>,
+{
+    let err = match time::timeout(CHECK_TIMEOUT, future).await {
+        Ok(Ok(())) => return Check::Ok,
+        Ok(Err(err)) => err.into(),
+        Err(_elapsed) => Error::TimedOut,
+    };
+
+    tracing::warn!(error = %err, ?err, "readiness check failed");
+
+    // The underlying errors can mention hosts and credentials, which this
+    // unauthenticated endpoint mustn't hand out
+    let reason = match err {
+        Error::Sqlx { .. } | Error::Migrate { .. } | Error::Session { .. } => {
+            String::from("unavailable")
+        }
+        Error::TimedOut | Error::DirtyMigration { .. } | Error::PendingMigration { .. } => {
+            err.to_string()
+        }
+    };
+
+    Check::Failed { reason }
+}
+
+async fn check_postgres(pg_pool: &PgPool) -> Result<(), Error>
+{
+    pg_pool.acquire().await?.ping().await?;
+
+    Ok(())
+}
+
+/// A database that is missing migrations this build expects, or that failed
+/// partway through one, would turn most requests into internal errors
+async fn check_migrations(pg_pool: &PgPool) -> Result<(), Error>
+{
+    let mut connection = pg_pool.acquire().await?;
+
+    if let Some(version) = connection.dirty_version().await? {
+        Err(Error::DirtyMigration { version })?;
+    }
+
+    let applied = connection.list_applied_migrations().await?;
+    let pending = sqlx::migrate!()
+        .iter()
+        .find(|migration| {
+            !applied
+                .iter()
+                .any(|applied| applied.version == migration.version)
+        })
+        .map(|migration| migration.version);
+
+    match pending {
+        Some(version) => Err(Error::PendingMigration { version }),
+        None => Ok(()),
+    }
+}
+
+#[derive(Debug, Error)]
+enum Error
+{
+    #[error("{inner}")]
+    Sqlx
+    {
+        #[from]
+        inner: sqlx::Error,
+    },
+    #[error("{inner}")]
+    Migrate
+    {
+        #[from]
+        inner: sqlx::migrate::MigrateError,
+    },
+    #[error("{inner}")]
+    Session
+    {
+        #[from]
+        inner: session::Error,
+    },
+    #[error("timed out")]
+    TimedOut,
+    #[error("migration {version} was only partially applied")]
+    DirtyMigration
+    {
+        version: i64,
+    },
+    #[error("migration {version} has not been applied")]
+    PendingMigration
+    {
+        version: i64,
+    },
+}
@@ -0,0 +1,218 @@
+use axum::{
+    routing::{delete, post},
+    Extension, Router,
+};
+
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{
+    audit::{self, AuditEvent},
+    db::DbPools,
+    http::{
+        self,
+        authz::{self, permission, RequirePermission},
+        client_info::ClientInfo,
+        locale, path,
+        session::{self, Session},
+    },
+};
+
+pub(in crate::http) fn router() -> Router
+{
+    Router::new().route("/auth/impersonation", delete(end_impersonation))
+}
+
+/// Starting to impersonate is up to admins, ending it up to whoever is
+/// impersonating
+pub(in crate::http) fn admin_router() -> Router
+{
+    Router::new().route("/admin/impersonate/:user_id", post(start_impersonation))
+}
+
+/// Swaps the admin's session for one acting as `user_id`, which remembers the
+/// admin so they can get back to their own account later
+async fn start_impersonation(
+    permission: RequirePermission<permission::UsersImpersonate>,
+    db: Extension<DbPools>,
+    audit_log: Extension<audit::Log>,
+    client: ClientInfo,
+    session_store: Extension<session::Store>,
+    session: session::extractor::Session,
+    path::extractor::Path(user_id): path::extractor::Path<Uuid>,
+) -> Result<(http::HeaderMap, http::StatusCode), http::Error>
+{
+    let impersonator_id = permission.user_id;
+
+    if user_id == impersonator_id {
+        Err(Error::CannotImpersonateSelf)?;
+    }
+
+    let disabled = sqlx::query_scalar!(r#"select disabled from users where user_id = $1"#, user_id)
+        .fetch_optional(db.writer())
+        .await?
+        .ok_or(Error::UserNotFound)?;
+    let grants = authz::Grants::load(db.writer(), user_id)
+        .await?
+        .ok_or(Error::UserNotFound)?;
+
+    // Impersonating another admin would hand over whatever they can do that
+    // isn't destructive, such as reading the audit log
+    if disabled {
+        Err(Error::CannotImpersonateDisabled)?;
+    }
+    if grants.roles.iter().any(|role| role == authz::ADMIN_ROLE) {
+        Err(Error::CannotImpersonateAdmin)?;
+    }
+
+    // Before any session changes, so there's no impersonating without it
+    // being on record
+    audit_log
+        .record(
+            db.writer(),
+            AuditEvent::ImpersonationStarted {
+                actor_id: impersonator_id,
+                user_id,
+            },
+            Some(&client.audit()),
+        )
+        .await?;
+
+    // The admin keeps reading errors in their own locale
+    let mut locale = None;
+    if let session::extractor::Session::Found(session) = session {
+        locale = session
+            .get::<locale::Locale>(locale::LOCALE_SESSION_KEY)
+            .await;
+        session_store.destroy_session(session).await?;
+    }
+
+    let mut session = Session::new();
+    session.insert("user_id", user_id).await?;
+    session
+        .insert(session::IMPERSONATOR_ID_SESSION_KEY, impersonator_id)
+        .await?;
+    session.insert(authz::GRANTS_SESSION_KEY, grants).await?;
+    session.insert(locale::LOCALE_SESSION_KEY, locale).await?;
+    // SAFETY: See relevant safety note for `auth::create_auth_session`
+    let cookie = session_store.store_session(session).await?.unwrap();
+
+    Ok((
+        session_store.set_cookie_headers(&cookie),
+        http::StatusCode::NO_CONTENT,
+    ))
+}
+
+/// Ends the impersonation and logs the admin back in as themselves, unless
+/// their own account was disabled or needs a new password meanwhile
+async fn end_impersonation(
+    db: Extension<DbPools>,
+    audit_log: Extension<audit::Log>,
+    client: ClientInfo,
+    session_store: Extension<session::Store>,
+    session: session::extractor::Session,
+) -> Result<(http::HeaderMap, http::StatusCode), http::Error>
+{
+    let session = match session {
+        session::extractor::Session::Found(session) => session,
+        session::extractor::Session::NotFound => Err(Error::NotImpersonating)?,
+    };
+
+    let user_id = session.get::<Uuid>("user_id").await;
+    let impersonator_id = session
+        .get::<Uuid>(session::IMPERSONATOR_ID_SESSION_KEY)
+        .await
+        .ok_or(Error::NotImpersonating)?;
+    let locale = session
+        .get::<locale::Locale>(locale::LOCALE_SESSION_KEY)
+        .await;
+
+    let impersonator = sqlx::query!(
+        r#"select disabled, password_reset_required from users where user_id = $1"#,
+        impersonator_id
+    )
+    .fetch_optional(db.writer())
+    .await?
+    .ok_or(Error::ImpersonatorNotFound)?;
+    let grants = authz::Grants::load(db.writer(), impersonator_id)
+        .await?
+        .ok_or(Error::ImpersonatorNotFound)?;
+
+    audit_log
+        .record(
+            db.writer(),
+            AuditEvent::ImpersonationEnded {
+                actor_id: impersonator_id,
+                user_id,
+            },
+            Some(&client.audit()),
+        )
+        .await?;
+
+    // Either way the impersonation is over
+    session_store.destroy_session(session).await?;
+
+    if impersonator.disabled {
+        Err(Error::AccountDisabled)?;
+    }
+    if impersonator.password_reset_required {
+        Err(Error::PasswordResetRequired)?;
+    }
+
+    let mut session = Session::new();
+    session.insert("user_id", impersonator_id).await?;
+    session.insert(authz::GRANTS_SESSION_KEY, grants).await?;
+    session.insert(locale::LOCALE_SESSION_KEY, locale).await?;
+    // SAFETY: See relevant safety note for `auth::create_auth_session`
+    let cookie = session_store.store_session(session).await?.unwrap();
+
+    Ok((
+        session_store.set_cookie_headers(&cookie),
+        http::StatusCode::NO_CONTENT,
+    ))
+}
+
+#[derive(Debug, Error)]
+enum Error
+{
+    #[error("no user with the provided id was found")]
+    UserNotFound,
+    #[error("the impersonating user no longer exists")]
+    ImpersonatorNotFound,
+    #[error("not currently impersonating another user")]
+    NotImpersonating,
+    #[error("cannot impersonate yourself")]
+    CannotImpersonateSelf,
+    #[error("cannot impersonate a disabled account")]
+    CannotImpersonateDisabled,
+    #[error("cannot impersonate another admin")]
+    CannotImpersonateAdmin,
+    #[error("this account has been disabled")]
+    AccountDisabled,
+    #[error("the password must be changed before logging in")]
+    PasswordResetRequired,
+}
+
+impl From<Error> for http::Error
+{
+    fn from(err: Error) -> Self
+    {
+        let error_code = match err {
+            Error::UserNotFound | Error::ImpersonatorNotFound => http::error::Code::USER_NOT_FOUND,
+            Error::NotImpersonating => http::error::Code::NOT_IMPERSONATING,
+            Error::CannotImpersonateSelf => http::error::Code::CANNOT_IMPERSONATE_SELF,
+            Error::CannotImpersonateDisabled => http::error::Code::CANNOT_IMPERSONATE_DISABLED,
+            Error::CannotImpersonateAdmin => http::error::Code::CANNOT_IMPERSONATE_ADMIN,
+            Error::AccountDisabled => http::error::Code::ACCOUNT_DISABLED,
+            Error::PasswordResetRequired => http::error::Code::PASSWORD_RESET_REQUIRED,
+        };
+
+        let message = err.to_string();
+
+        http::Error {
+            error_code,
+            message,
+            errors: Vec::new(),
+        }
+    }
+}
@@ -1,5 +1,7 @@
 pub(in crate::http) mod extractor
 {
+    use std::error::Error as StdError;
+
     use crate::http::{self, error};
 
     use axum::extract::rejection;
@@ -20,32 +22,95 @@ pub(in crate::http) mod extractor
                 rejection::JsonRejection::MissingJsonContentType(_) => {
                     error::Code::JSON_MISSING_CONTENT_TYPE
                 }
+                _ if is_too_large(&rejection) => error::Code::PAYLOAD_TOO_LARGE,
                 _ => error::Code::INTERNAL_SERVER_ERROR,
             };
 
-            let status_code = match rejection {
-                rejection::JsonRejection::JsonSyntaxError(_) => http::StatusCode::BAD_REQUEST,
-                rejection::JsonRejection::JsonDataError(_) => {
-                    http::StatusCode::UNPROCESSABLE_ENTITY
-                }
-                rejection::JsonRejection::MissingJsonContentType(_) => {
-                    http::StatusCode::UNSUPPORTED_MEDIA_TYPE
-                }
-                _ => http::StatusCode::INTERNAL_SERVER_ERROR,
-            };
-
             let message = match rejection {
                 rejection::JsonRejection::JsonSyntaxError(_)
                 | rejection::JsonRejection::JsonDataError(_)
                 | rejection::JsonRejection::MissingJsonContentType(_) => rejection.to_string(),
-                _ => String::from(http::error::INTERNAL_SERVER_ERROR_MESSAGE),
+                _ if is_too_large(&rejection) => {
+                    String::from(error::Code::PAYLOAD_TOO_LARGE.message())
+                }
+                _ => {
+                    tracing::error!(error = %rejection, "unexpected json rejection");
+                    String::from(http::error::INTERNAL_SERVER_ERROR_MESSAGE)
+                }
+            };
+
+            let errors = match &rejection {
+                rejection::JsonRejection::JsonDataError(data_err) => field_errors(data_err),
+                _ => Vec::new(),
             };
 
             http::Error {
                 error_code,
-                status_code,
                 message,
+                errors,
             }
         }
     }
+
+    /// Whether the body went over the limit set with `DefaultBodyLimit` while
+    /// being read
+    fn is_too_large(rejection: &rejection::JsonRejection) -> bool
+    {
+        matches!(
+            rejection,
+            rejection::JsonRejection::BytesRejection(
+                rejection::BytesRejection::FailedToBufferBody(
+                    rejection::FailedToBufferBody::LengthLimitError(_)
+                )
+            )
+        )
+    }
+
+    /// Digs the field that failed out of a data error, serde reports it
+    /// along with the path to it
+    fn field_errors(data_err: &rejection::JsonDataError) -> Vec<error::FieldError>
+    {
+        let mut source = StdError::source(data_err);
+        while let Some(err) = source {
+            if let Some(path_err) =
+                err.downcast_ref::<serde_path_to_error::Error<serde_json::Error>>()
+            {
+                return vec![field_error(path_err)];
+            }
+            source = err.source();
+        }
+
+        Vec::new()
+    }
+
+    fn field_error(path_err: &serde_path_to_error::Error<serde_json::Error>) -> error::FieldError
+    {
+        let inner = path_err.inner();
+        let path = path_err.path().to_string();
+
+        // The position serde_json appends to its messages means little to
+        // whoever built the object, the field path says it better
+        let message = inner.to_string();
+        let position = format!(" at line {} column {}", inner.line(), inner.column());
+        let message = message.strip_suffix(&position).unwrap_or(&message);
+
+        // Missing and unknown fields are reported at the object holding them
+        // rather than at the field itself
+        let field_name = ["missing field `", "unknown field `"]
+            .iter()
+            .find_map(|prefix| message.strip_prefix(prefix))
+            .and_then(|rest| rest.split_once('`'))
+            .map(|(field_name, _rest)| field_name);
+
+        let field = match (path.as_str(), field_name) {
+            (".", Some(field_name)) => String::from(field_name),
+            (path, Some(field_name)) => format!("{path}.{field_name}"),
+            (path, None) => String::from(path),
+        };
+
+        error::FieldError {
+            field,
+            message: String::from(message),
+        }
+    }
 }
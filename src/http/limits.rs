@@ -0,0 +1,95 @@
+use std::any::Any;
+
+use axum::{
+    http::Request,
+    middleware::Next,
+    response::{self, IntoResponse},
+    BoxError, Extension,
+};
+use thiserror::Error;
+use tower::load_shed::error::Overloaded;
+
+use crate::{config, http};
+
+/// Gives up on requests that take longer than `limits.request_timeout_secs`,
+/// so a stuck query can't hold on to a connection for good
+pub(in crate::http) async fn time_out<B>(
+    config: Extension<config::Shared>,
+    req: Request<B>,
+    next: Next<B>,
+) -> response::Response
+{
+    let request_timeout = config.load().limits().request_timeout();
+
+    match tokio::time::timeout(request_timeout, next.run(req)).await {
+        Ok(res) => res,
+        Err(_elapsed) => http::Error::from(Error::TimedOut).into_response(),
+    }
+}
+
+/// Turns the requests refused by load shedding into a response, as the
+/// `HandleErrorLayer` in front of it requires
+pub(in crate::http) async fn shed(err: BoxError) -> http::Error
+{
+    if err.is::<Overloaded>() {
+        Error::Overloaded.into()
+    } else {
+        Error::Unexpected(err).into()
+    }
+}
+
+/// Responds to a panic in a handler like to any other internal error, rather
+/// than dropping the connection
+pub(in crate::http) fn panicked(err: Box<dyn Any + Send + 'static>) -> response::Response
+{
+    let details = err
+        .downcast_ref::<String>()
+        .map(String::as_str)
+        .or_else(|| err.downcast_ref::<&str>().copied())
+        .unwrap_or("no details");
+
+    http::Error::from(Error::Panicked(String::from(details))).into_response()
+}
+
+#[derive(Debug, Error)]
+enum Error
+{
+    #[error("the request took too long to handle")]
+    TimedOut,
+    #[error("the server is handling too many requests, try again later")]
+    Overloaded,
+    #[error("handler panicked: {0}")]
+    Panicked(String),
+    #[error("unexpected load shedding error: {0}")]
+    Unexpected(BoxError),
+}
+
+impl From<Error> for http::Error
+{
+    fn from(err: Error) -> Self
+    {
+        let error_code = match err {
+            Error::TimedOut => http::error::Code::REQUEST_TIMED_OUT,
+            Error::Overloaded => http::error::Code::OVERLOADED,
+            Error::Panicked(_) | Error::Unexpected(_) => http::error::Code::INTERNAL_SERVER_ERROR,
+        };
+
+        let message = match err {
+            Error::TimedOut => {
+                tracing::warn!(error = %err, "request timed out");
+                err.to_string()
+            }
+            Error::Overloaded => err.to_string(),
+            Error::Panicked(_) | Error::Unexpected(_) => {
+                tracing::error!(error = %err, "limits error");
+                String::from(http::error::INTERNAL_SERVER_ERROR_MESSAGE)
+            }
+        };
+
+        http::Error {
+            error_code,
+            message,
+            errors: Vec::new(),
+        }
+    }
+}
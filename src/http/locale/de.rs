@@ -0,0 +1,33 @@
+use super::{Catalog, Code};
+
+pub(super) const CATALOG: Catalog = catalog! {
+    JSON_SYNTAX_ERROR => "der Anfragetext ist kein gültiges JSON";
+    JSON_DATA_ERROR => "der Anfragetext enthält nicht die erwarteten Felder";
+    JSON_MISSING_CONTENT_TYPE => "erwartet wird eine Anfrage mit `Content-Type: application/json`";
+    PAYLOAD_TOO_LARGE => "der Anfragetext ist zu groß";
+    NO_SESSION_FOUND => "keine Sitzung gefunden";
+    INVALID_PATH_PARAMETER => "ein Pfadparameter ist ungültig";
+    INVALID_QUERY_PARAMETER => "ein Abfrageparameter ist ungültig";
+    API_VERSION_GONE => "diese Version der API wird nicht mehr bereitgestellt";
+    USER_NOT_FOUND => "kein solcher Benutzer gefunden";
+    WRONG_PASSWORD => "das angegebene Passwort ist falsch";
+    MUST_BE_AUTHENTICATED => "Anmeldung erforderlich";
+    FORBIDDEN => "die für diese Aktion erforderliche Berechtigung fehlt";
+    ACCOUNT_DISABLED => "dieses Konto wurde deaktiviert";
+    PASSWORD_RESET_REQUIRED => "das Passwort muss vor der Anmeldung geändert werden";
+    FORBIDDEN_WHILE_IMPERSONATING => "während eines Identitätswechsels nicht erlaubt";
+    NOT_IMPERSONATING => "derzeit findet kein Identitätswechsel statt";
+    CANNOT_IMPERSONATE_SELF => "ein Identitätswechsel zu sich selbst ist nicht möglich";
+    TOO_MANY_LOGIN_ATTEMPTS => "zu viele fehlgeschlagene Anmeldeversuche, bitte später erneut versuchen";
+    CROSS_SITE_REQUEST => "Anfragen, die etwas ändern, sind von diesem Ursprung aus nicht erlaubt";
+    CANNOT_IMPERSONATE_DISABLED => "ein deaktiviertes Konto kann nicht übernommen werden";
+    CANNOT_IMPERSONATE_ADMIN => "ein Identitätswechsel zu einem anderen Administrator ist nicht möglich";
+    USERNAME_TAKEN => "Benutzername bereits vergeben";
+    PASSWORD_BREACHED => "das Passwort taucht in einem bekannten Datenleck auf";
+    REGISTRATION_DISABLED => "die Registrierung neuer Benutzer ist deaktiviert";
+    ROLE_NOT_FOUND => "keine Rolle mit dem angegebenen Namen gefunden";
+    REQUEST_TIMED_OUT => "die Bearbeitung der Anfrage hat zu lange gedauert";
+    OVERLOADED => "der Server bearbeitet zu viele Anfragen, bitte später erneut versuchen";
+    SHUTTING_DOWN => "der Server wurde beendet, bevor die Anfrage bearbeitet werden konnte, bitte erneut versuchen";
+    INTERNAL_SERVER_ERROR => "Interner Serverfehler";
+};
@@ -0,0 +1,33 @@
+use super::{Catalog, Code};
+
+pub(super) const CATALOG: Catalog = catalog! {
+    JSON_SYNTAX_ERROR => "el cuerpo de la solicitud no es JSON válido";
+    JSON_DATA_ERROR => "el cuerpo de la solicitud no tiene los campos esperados";
+    JSON_MISSING_CONTENT_TYPE => "se esperaba una solicitud con `Content-Type: application/json`";
+    PAYLOAD_TOO_LARGE => "el cuerpo de la solicitud es demasiado grande";
+    NO_SESSION_FOUND => "no se encontró ninguna sesión";
+    INVALID_PATH_PARAMETER => "un parámetro de la ruta no es válido";
+    INVALID_QUERY_PARAMETER => "un parámetro de la consulta no es válido";
+    API_VERSION_GONE => "esta versión de la API ya no está disponible";
+    USER_NOT_FOUND => "no se encontró el usuario";
+    WRONG_PASSWORD => "la contraseña proporcionada es incorrecta";
+    MUST_BE_AUTHENTICATED => "se requiere autenticación";
+    FORBIDDEN => "falta el permiso necesario para esta acción";
+    ACCOUNT_DISABLED => "esta cuenta ha sido desactivada";
+    PASSWORD_RESET_REQUIRED => "la contraseña debe cambiarse antes de iniciar sesión";
+    FORBIDDEN_WHILE_IMPERSONATING => "no está permitido mientras se suplanta a otro usuario";
+    NOT_IMPERSONATING => "no se está suplantando a ningún otro usuario";
+    CANNOT_IMPERSONATE_SELF => "no puede suplantarse a sí mismo";
+    TOO_MANY_LOGIN_ATTEMPTS => "demasiados intentos fallidos de inicio de sesión, inténtelo de nuevo más tarde";
+    CROSS_SITE_REQUEST => "no se permiten solicitudes que modifiquen algo desde este origen";
+    CANNOT_IMPERSONATE_DISABLED => "no se puede suplantar una cuenta desactivada";
+    CANNOT_IMPERSONATE_ADMIN => "no se puede suplantar a otro administrador";
+    USERNAME_TAKEN => "el nombre de usuario ya está en uso";
+    PASSWORD_BREACHED => "la contraseña aparece en una filtración de datos conocida";
+    REGISTRATION_DISABLED => "el registro de nuevos usuarios está desactivado";
+    ROLE_NOT_FOUND => "no se encontró ningún rol con el nombre proporcionado";
+    REQUEST_TIMED_OUT => "la solicitud tardó demasiado en procesarse";
+    OVERLOADED => "el servidor está atendiendo demasiadas solicitudes, inténtalo de nuevo más tarde";
+    SHUTTING_DOWN => "el servidor se detuvo antes de poder atender la solicitud, inténtalo de nuevo";
+    INTERNAL_SERVER_ERROR => "Error interno del servidor";
+};
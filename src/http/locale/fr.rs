@@ -0,0 +1,33 @@
+use super::{Catalog, Code};
+
+pub(super) const CATALOG: Catalog = catalog! {
+    JSON_SYNTAX_ERROR => "le corps de la requête n'est pas du JSON valide";
+    JSON_DATA_ERROR => "le corps de la requête ne contient pas les champs attendus";
+    JSON_MISSING_CONTENT_TYPE => "une requête avec `Content-Type: application/json` est attendue";
+    PAYLOAD_TOO_LARGE => "le corps de la requête est trop volumineux";
+    NO_SESSION_FOUND => "aucune session trouvée";
+    INVALID_PATH_PARAMETER => "un paramètre du chemin est invalide";
+    INVALID_QUERY_PARAMETER => "un paramètre de la requête est invalide";
+    API_VERSION_GONE => "cette version de l'API n'est plus disponible";
+    USER_NOT_FOUND => "aucun utilisateur correspondant n'a été trouvé";
+    WRONG_PASSWORD => "le mot de passe fourni est incorrect";
+    MUST_BE_AUTHENTICATED => "authentification requise";
+    FORBIDDEN => "la permission requise pour cette action est manquante";
+    ACCOUNT_DISABLED => "ce compte a été désactivé";
+    PASSWORD_RESET_REQUIRED => "le mot de passe doit être changé avant de se connecter";
+    FORBIDDEN_WHILE_IMPERSONATING => "non autorisé lors de l'emprunt de l'identité d'un autre utilisateur";
+    NOT_IMPERSONATING => "aucune identité d'un autre utilisateur n'est actuellement empruntée";
+    CANNOT_IMPERSONATE_SELF => "impossible d'emprunter sa propre identité";
+    TOO_MANY_LOGIN_ATTEMPTS => "trop de tentatives de connexion échouées, réessayez plus tard";
+    CROSS_SITE_REQUEST => "les requêtes qui modifient quelque chose ne sont pas autorisées depuis cette origine";
+    CANNOT_IMPERSONATE_DISABLED => "impossible d'emprunter l'identité d'un compte désactivé";
+    CANNOT_IMPERSONATE_ADMIN => "impossible d'emprunter l'identité d'un autre administrateur";
+    USERNAME_TAKEN => "nom d'utilisateur déjà pris";
+    PASSWORD_BREACHED => "le mot de passe figure dans une fuite de données connue";
+    REGISTRATION_DISABLED => "l'inscription de nouveaux utilisateurs est désactivée";
+    ROLE_NOT_FOUND => "aucun rôle portant le nom fourni n'a été trouvé";
+    REQUEST_TIMED_OUT => "le traitement de la requête a pris trop de temps";
+    OVERLOADED => "le serveur traite trop de requêtes, réessayez plus tard";
+    SHUTTING_DOWN => "le serveur s'est arrêté avant d'avoir pu traiter la requête, réessayez";
+    INTERNAL_SERVER_ERROR => "Erreur interne du serveur";
+};
@@ -0,0 +1,162 @@
+use std::cell::Cell;
+
+use axum::{
+    http::{header, Request},
+    middleware::Next,
+    response,
+};
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::http::error::Code;
+
+/// Declares the translation of each error code's message into a locale, any
+/// code left out falls back to the English message
+macro_rules! catalog {
+    ($($name:ident => $message:literal;)*) => {
+        &[$((Code::$name, $message)),*]
+    };
+}
+
+mod de;
+mod es;
+mod fr;
+
+/// Pairs error codes with their message in a single locale
+type Catalog = &'static [(Code, &'static str)];
+
+/// Set on sessions of users who picked a locale of their own
+pub(in crate::http) const LOCALE_SESSION_KEY: &str = "locale";
+
+tokio::task_local! {
+    static NEGOTIATED: Negotiated;
+}
+
+/// A locale error messages can be rendered in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub(in crate::http) enum Locale
+{
+    De,
+    En,
+    Es,
+    Fr,
+}
+
+impl Locale
+{
+    const ALL: [Locale; 4] = [Locale::De, Locale::En, Locale::Es, Locale::Fr];
+
+    /// The language tag the locale is identified by, as in `Accept-Language`
+    /// and `Content-Language`
+    pub(in crate::http) fn tag(self) -> &'static str
+    {
+        match self {
+            Locale::De => "de",
+            Locale::En => "en",
+            Locale::Es => "es",
+            Locale::Fr => "fr",
+        }
+    }
+
+    /// Matches on the primary language only, so regional variants like
+    /// `fr-CA` get the `fr` catalog
+    pub(in crate::http) fn from_tag(tag: &str) -> Option<Locale>
+    {
+        let language = tag.split('-').next().unwrap_or(tag);
+
+        Locale::ALL
+            .into_iter()
+            .find(|locale| locale.tag().eq_ignore_ascii_case(language))
+    }
+
+    /// The message of `code` in this locale, `None` if it hasn't been
+    /// translated
+    pub(in crate::http) fn message(self, code: Code) -> Option<&'static str>
+    {
+        let catalog: Catalog = match self {
+            Locale::De => de::CATALOG,
+            // The English messages come from the errors themselves
+            Locale::En => return None,
+            Locale::Es => es::CATALOG,
+            Locale::Fr => fr::CATALOG,
+        };
+
+        catalog
+            .iter()
+            .find(|(translated, _)| translated.code() == code.code())
+            .map(|(_, message)| *message)
+    }
+}
+
+#[derive(Debug)]
+struct Negotiated
+{
+    accepted: Option<Locale>,
+    /// Only known once the session has been loaded, which is up to the
+    /// handler
+    preferred: Cell<Option<Locale>>,
+}
+
+/// The locale to render errors of the current request in: the one the user
+/// picked, else the best match for `Accept-Language`, else English
+pub(in crate::http) fn current() -> Locale
+{
+    NEGOTIATED
+        .try_with(|negotiated| negotiated.preferred.get().or(negotiated.accepted))
+        .ok()
+        .flatten()
+        .unwrap_or(Locale::En)
+}
+
+/// Records the locale the user picked, to take precedence over
+/// `Accept-Language` for the rest of the request
+pub(in crate::http) fn prefer(locale: Locale)
+{
+    let _negotiated = NEGOTIATED.try_with(|negotiated| negotiated.preferred.set(Some(locale)));
+}
+
+/// Makes the locale negotiated from `Accept-Language` available to `current`
+/// for as long as the request is being handled
+pub(in crate::http) async fn negotiate<B>(req: Request<B>, next: Next<B>) -> response::Response
+{
+    let accepted = req
+        .headers()
+        .get_all(header::ACCEPT_LANGUAGE)
+        .iter()
+        .filter_map(|accept_language| accept_language.to_str().ok())
+        .flat_map(|accept_language| accept_language.split(','))
+        .filter_map(language_range)
+        // The first of the most preferred ones wins, `max_by` would take the
+        // last
+        .fold(
+            None,
+            |best: Option<(Locale, f32)>, (locale, q)| match best {
+                Some((_, best_q)) if best_q >= q => best,
+                _ => Some((locale, q)),
+            },
+        )
+        .map(|(locale, _)| locale);
+
+    let negotiated = Negotiated {
+        accepted,
+        preferred: Cell::new(None),
+    };
+
+    NEGOTIATED.scope(negotiated, next.run(req)).await
+}
+
+/// The locale a single language range from `Accept-Language` names along
+/// with its weight, `None` for unsupported or refused ones
+fn language_range(language_range: &str) -> Option<(Locale, f32)>
+{
+    let mut params = language_range.split(';').map(str::trim);
+
+    let locale = params.next().and_then(Locale::from_tag)?;
+    let q = params
+        .find_map(|param| param.strip_prefix("q="))
+        .map_or(Some(1.0), |q| q.parse::<f32>().ok())?;
+
+    (q > 0.0).then_some((locale, q))
+}
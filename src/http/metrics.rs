@@ -0,0 +1,64 @@
+use std::time::Instant;
+
+use axum::{
+    extract::MatchedPath,
+    http::{header, Request},
+    middleware::Next,
+    response,
+    routing::get,
+    Extension, Router,
+};
+
+use crate::{db::DbPools, metrics};
+
+pub(in crate::http) fn router() -> Router
+{
+    Router::new().route("/metrics", get(render_metrics))
+}
+
+async fn render_metrics(db: Extension<DbPools>) -> impl response::IntoResponse
+{
+    let pools = [("primary", Some(db.writer())), ("replica", db.replica())];
+    for (pool, pg_pool) in pools {
+        if let Some(pg_pool) = pg_pool {
+            let size = i64::from(pg_pool.size());
+            let idle = pg_pool.num_idle() as i64;
+            metrics::PG_POOL_CONNECTIONS
+                .with_label_values(&[pool, "active"])
+                .set(size - idle);
+            metrics::PG_POOL_CONNECTIONS
+                .with_label_values(&[pool, "idle"])
+                .set(idle);
+        }
+    }
+
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics::render(),
+    )
+}
+
+/// Records the count and latency of every request, labelled by the route
+/// template that matched rather than the raw path so ids don't explode the
+/// number of series
+pub(in crate::http) async fn track<B>(req: Request<B>, next: Next<B>) -> response::Response
+{
+    let method = req.method().to_string();
+    let route = req.extensions().get::<MatchedPath>().map_or_else(
+        || String::from("unmatched"),
+        |path| String::from(path.as_str()),
+    );
+
+    let start = Instant::now();
+    let res = next.run(req).await;
+    let elapsed = start.elapsed();
+
+    metrics::HTTP_REQUESTS
+        .with_label_values(&[&method, &route, res.status().as_str()])
+        .inc();
+    metrics::HTTP_REQUEST_DURATION
+        .with_label_values(&[&method, &route])
+        .observe(elapsed.as_secs_f64());
+
+    res
+}
@@ -1,17 +1,69 @@
-use std::net::SocketAddr;
+use std::{
+    fs,
+    future::Future,
+    io,
+    net::{self, SocketAddr},
+    panic,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use axum::{Extension, Router, Server};
-use sqlx::PgPool;
-use tokio::signal;
-use tower_http::cors::CorsLayer;
+use axum::{
+    error_handling::HandleErrorLayer, extract::DefaultBodyLimit, middleware, Extension, Router,
+    Server,
+};
+use thiserror::Error;
+use tokio::{net::TcpListener, signal, task::JoinSet};
+use tower::{limit::GlobalConcurrencyLimitLayer, ServiceBuilder};
+use tower_http::{
+    catch_panic::CatchPanicLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::{DefaultOnResponse, TraceLayer},
+};
+use tracing::Level;
 
+use crate::{
+    config,
+    db::DbPools,
+    password::{self, breach},
+};
+
+mod client_info;
+mod cors;
+mod csrf;
 mod error;
 pub(in crate::http) use error::Error;
 
 mod json;
+mod limits;
+mod locale;
+mod metrics;
+mod openapi;
+mod path;
+mod query;
+mod rate_limit;
+pub use rate_limit::LoginLimiter;
+
+mod replica;
+mod request_id;
+mod security_headers;
 pub mod session;
+mod shutdown;
+pub use shutdown::Shutdown;
+
+mod tls;
+pub use tls::Error as TlsError;
+#[cfg(unix)]
+mod unix;
+
+mod version;
 
+mod admin;
+mod audit;
 mod auth;
+mod authz;
+mod health;
+mod impersonation;
 mod users;
 
 type StatusCode = ::axum::http::StatusCode;
@@ -20,55 +72,360 @@ use axum::http::header;
 type HeaderMap = ::axum::http::HeaderMap;
 type HeaderValue = ::axum::http::HeaderValue;
 
-fn app(cors: CorsLayer, pg_pool: PgPool, session_store: session::Store) -> Router
+const IMPERSONATOR_ID_HEADER: &str = "x-impersonator-id";
+
+/// How long to wait before accepting again after failing to, which mostly
+/// happens when out of file descriptors
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_secs(1);
+
+/// The servers `serve` runs side by side, one per listener
+type Servers = JoinSet<Result<(), hyper::Error>>;
+
+/// The API's routes along with every layer they rely on, as served by
+/// `serve`. The admin API is left to `admin_app` when there's an admin
+/// listener.
+pub fn app(
+    config: config::Shared,
+    db: DbPools,
+    session_store: session::Store,
+    login_limiter: LoginLimiter,
+    breach_checker: breach::Checker,
+    shutdown: Shutdown,
+) -> Router
+{
+    let routes = match config.load().admin() {
+        Some(_) => version::Routes::Public,
+        None => version::Routes::All,
+    };
+
+    // Only the API itself is versioned, not what's there to operate it or to
+    // describe it
+    let router = Router::new()
+        .merge(version::router(&config.load(), routes))
+        .merge(error::router())
+        .merge(openapi::router());
+
+    with_layers(
+        router,
+        config,
+        db,
+        session_store,
+        login_limiter,
+        breach_checker,
+        shutdown,
+    )
+}
+
+/// The admin API along with every layer it relies on, as served on the admin
+/// listener by `serve`.
+///
+/// Its requests count towards a concurrency limit of their own, so a busy API
+/// can't keep operators out.
+pub fn admin_app(
+    config: config::Shared,
+    db: DbPools,
+    session_store: session::Store,
+    login_limiter: LoginLimiter,
+    breach_checker: breach::Checker,
+    shutdown: Shutdown,
+) -> Router
 {
-    Router::new()
-        .merge(auth::router())
-        .merge(users::router())
-        .layer(Extension(pg_pool))
+    let router = Router::new().merge(version::router(&config.load(), version::Routes::Admin));
+
+    with_layers(
+        router,
+        config,
+        db,
+        session_store,
+        login_limiter,
+        breach_checker,
+        shutdown,
+    )
+}
+
+/// Wraps `router` in every layer its routes rely on, innermost first, and adds
+/// the health checks alongside
+fn with_layers(
+    router: Router,
+    config: config::Shared,
+    db: DbPools,
+    session_store: session::Store,
+    login_limiter: LoginLimiter,
+    breach_checker: breach::Checker,
+    shutdown: Shutdown,
+) -> Router
+{
+    let cors = cors::layer(config.clone());
+    let password_hasher = password::Hasher::new(config.load().argon2_params());
+    let audit_log = crate::audit::Log::new(config.load().audit_key());
+    let limits = *config.load().limits();
+
+    router
+        .layer(CatchPanicLayer::custom(limits::panicked))
+        .layer(middleware::from_fn(limits::time_out))
+        .layer(middleware::from_fn(shutdown::cut_off))
+        // Refused right away rather than queued, a client is better off
+        // retrying than waiting on a backlog that may never clear. The
+        // semaphore is shared by every route the layer is applied to.
+        .layer(
+            ServiceBuilder::new()
+                .layer(HandleErrorLayer::new(limits::shed))
+                .load_shed()
+                .layer(GlobalConcurrencyLimitLayer::new(
+                    limits.max_concurrent_requests(),
+                )),
+        )
+        .layer(DefaultBodyLimit::max(limits.body()))
+        // Kept out of the limits above, a busy app would otherwise be taken
+        // for a dead one and restarted when it is most needed
+        .merge(health::router().layer(CatchPanicLayer::custom(limits::panicked)))
+        .layer(middleware::from_fn(csrf::check_origin))
+        .layer(middleware::from_fn(metrics::track))
+        .layer(middleware::from_fn(replica::pin_after_writes))
+        .layer(Extension(config.clone()))
+        .layer(Extension(db))
         .layer(Extension(session_store))
+        .layer(Extension(login_limiter))
+        .layer(Extension(password_hasher))
+        .layer(Extension(audit_log))
+        .layer(Extension(breach_checker))
+        .layer(Extension(shutdown.clone()))
+        .layer(middleware::from_fn(error::negotiate))
+        .layer(middleware::from_fn(locale::negotiate))
+        .layer(middleware::from_fn(request_id::scope))
+        .layer(PropagateRequestIdLayer::new(request_id::REQUEST_ID_HEADER))
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(request_id::make_span)
+                .on_response(DefaultOnResponse::new().level(Level::INFO)),
+        )
+        .layer(SetRequestIdLayer::new(
+            request_id::REQUEST_ID_HEADER,
+            MakeRequestUuid,
+        ))
         .layer(cors)
+        .layer(middleware::from_fn_with_state(
+            config.clone(),
+            security_headers::set,
+        ))
+        .layer(middleware::from_fn_with_state(config, client_info::resolve))
+        .layer(middleware::from_fn_with_state(shutdown, shutdown::track))
+}
+
+fn metrics_app(db: DbPools) -> Router
+{
+    metrics::router().layer(Extension(db))
 }
 
+/// Serves the app on every listener in the config until `signal` resolves,
+/// then shuts down as `config.shutdown()` says and closes the database pools.
+///
+/// Redis connections are opened for each operation rather than pooled, so
+/// they are closed along with the requests that opened them.
 pub async fn serve(
-    in_production: bool,
-    port: u16,
-    pg_pool: PgPool,
-    session_store: session::Store,
-) -> Result<(), hyper::Error>
+    config: config::Shared,
+    db: DbPools,
+    redis_client: redis::Client,
+    breach_checker: breach::Checker,
+    signal: impl Future<Output = ()>,
+) -> Result<(), ServeError>
 {
-    let addr = if in_production {
-        SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 0], port))
-    } else {
-        SocketAddr::from(([127, 0, 0, 1], port))
-    };
+    // Only settings that can't be reloaded are read from this, the rest is
+    // read from `config` whenever needed
+    let startup_config = config.load();
+
+    let session_store = session::Store::new(redis_client.clone(), startup_config.cookie().clone());
+    let login_limiter = LoginLimiter::new(redis_client, config.clone());
+    let shutdown = Shutdown::new();
+
+    let api = app(
+        config.clone(),
+        db.clone(),
+        session_store.clone(),
+        login_limiter.clone(),
+        breach_checker.clone(),
+        shutdown.clone(),
+    );
+    let mut servers = Servers::new();
+
+    match startup_config.admin() {
+        // Kept off the API's listeners so only the private network can reach
+        // them
+        Some(admin) => {
+            let admin_app = admin_app(
+                config.clone(),
+                db.clone(),
+                session_store,
+                login_limiter,
+                breach_checker,
+                shutdown.clone(),
+            )
+            .merge(metrics_app(db.clone()));
 
-    let cors_origin = if in_production {
-        ::std::env::var("CORS_ORIGIN").unwrap()
-    } else {
-        format!("http://127.0.0.1:3000")
+            if let Some(port) = admin.port() {
+                let addr = SocketAddr::new(admin.address(), port);
+                serve_tcp(&mut servers, &shutdown, "admin", addr, admin_app.clone())?;
+            }
+            if let Some(path) = admin.unix_socket() {
+                serve_unix(&mut servers, &shutdown, "admin", path, admin_app)?;
+            }
+        }
+        // Metrics say too much about the app to be served to anyone, and
+        // production configs without an admin listener are refused
+        None => tracing::warn!("no admin listener, `/metrics` isn't served"),
     }
-    .parse::<HeaderValue>()
-    .unwrap();
-    let cors = CorsLayer::new()
-        .allow_methods([
-            ::axum::http::Method::GET,
-            ::axum::http::Method::POST,
-            ::axum::http::Method::DELETE,
-        ])
-        .allow_credentials(true)
-        .allow_origin(cors_origin)
-        .allow_headers([::axum::http::header::CONTENT_TYPE]);
-
-    Server::bind(&addr)
-        .serve(app(cors, pg_pool, session_store).into_make_service())
-        .with_graceful_shutdown(shutdown_signal())
-        .await?;
+
+    let addr = SocketAddr::new(startup_config.address(), startup_config.port());
+    match startup_config.tls() {
+        Some(tls) => {
+            let listener = TcpListener::bind(addr)
+                .await
+                .map_err(|inner| ServeError::Bind { addr, inner })?;
+            let incoming = tls::Incoming::new(listener, tls)?;
+            let server = Server::builder(incoming)
+                .serve(
+                    api.clone()
+                        .layer(Extension(client_info::Scheme::Https))
+                        .into_make_service_with_connect_info::<SocketAddr>(),
+                )
+                .with_graceful_shutdown(shutdown.stopping());
+            tracing::info!(listener = "api", %addr, "listening over HTTPS");
+            let _server = servers.spawn(server);
+
+            if let Some(redirect_port) = tls.redirect_port() {
+                let redirect_addr = SocketAddr::new(addr.ip(), redirect_port);
+                let redirect_app = tls::redirect_app(addr.port());
+                serve_tcp(
+                    &mut servers,
+                    &shutdown,
+                    "redirect",
+                    redirect_addr,
+                    redirect_app,
+                )?;
+            }
+        }
+        None => serve_tcp(&mut servers, &shutdown, "api", addr, api.clone())?,
+    }
+
+    if let Some(path) = startup_config.unix_socket() {
+        serve_unix(&mut servers, &shutdown, "api", path, api)?;
+    }
+
+    // A server failing before then returns right away, dropping the others
+    // along with the set
+    tokio::select! {
+        Some(server) = servers.join_next() => {
+            server.unwrap_or_else(|err| panic::resume_unwind(err.into_panic()))?;
+        }
+        () = signal => {}
+    }
+
+    shutdown.drain(config.load().shutdown(), &mut servers).await;
+
+    tracing::info!("closing database connections");
+    db.close().await;
+    tracing::info!("shut down");
+
+    Ok(())
+}
+
+/// Serves `app` over plain HTTP on `addr` until shut down
+fn serve_tcp(
+    servers: &mut Servers,
+    shutdown: &Shutdown,
+    listener: &'static str,
+    addr: SocketAddr,
+    app: Router,
+) -> Result<(), ServeError>
+{
+    let tcp_listener =
+        net::TcpListener::bind(addr).map_err(|inner| ServeError::Bind { addr, inner })?;
+    let server = Server::from_tcp(tcp_listener)?
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(shutdown.stopping());
+
+    tracing::info!(listener, %addr, "listening");
+    let _server = servers.spawn(server);
+
+    Ok(())
+}
+
+/// Serves `app` over plain HTTP on the Unix domain socket at `path` until
+/// shut down, removing the socket then
+#[cfg(unix)]
+fn serve_unix(
+    servers: &mut Servers,
+    shutdown: &Shutdown,
+    listener: &'static str,
+    path: &Path,
+    app: Router,
+) -> Result<(), ServeError>
+{
+    let incoming = unix::Incoming::bind(path).map_err(|inner| ServeError::BindUnix {
+        path: path.to_owned(),
+        inner,
+    })?;
+    let server = Server::builder(incoming)
+        .serve(app.into_make_service())
+        .with_graceful_shutdown(shutdown.stopping());
+
+    tracing::info!(listener, path = %path.display(), "listening");
+    let path = path.to_owned();
+    let _server = servers.spawn(async move {
+        let served = server.await;
+        let _removed = fs::remove_file(&path);
+        served
+    });
 
     Ok(())
 }
 
-async fn shutdown_signal()
+#[cfg(not(unix))]
+fn serve_unix(
+    _servers: &mut Servers,
+    _shutdown: &Shutdown,
+    _listener: &'static str,
+    _path: &Path,
+    _app: Router,
+) -> Result<(), ServeError>
+{
+    unreachable!("`Config::load` refuses Unix sockets on other platforms")
+}
+
+/// Why `serve` couldn't start serving or stopped before being asked to
+#[derive(Debug, Error)]
+pub enum ServeError
+{
+    #[error("could not listen on {addr}: {inner}")]
+    Bind
+    {
+        addr: SocketAddr,
+        #[source]
+        inner: io::Error,
+    },
+    #[error("could not listen on {}: {inner}", .path.display())]
+    BindUnix
+    {
+        path: PathBuf,
+        #[source]
+        inner: io::Error,
+    },
+    #[error("{inner}")]
+    Tls
+    {
+        #[from]
+        inner: TlsError,
+    },
+    #[error("{inner}")]
+    Hyper
+    {
+        #[from]
+        inner: hyper::Error,
+    },
+}
+
+/// Resolves on Ctrl+C or SIGTERM, for `serve` to start shutting down
+pub async fn shutdown_signal()
 {
     let ctrl_c = async {
         signal::ctrl_c()
@@ -0,0 +1,123 @@
+use std::collections::BTreeMap;
+
+use axum::{routing::get, Json, Router};
+use once_cell::sync::Lazy;
+use utoipa::{
+    openapi::{
+        path::Operation, Content, OpenApi as Document, Ref, RefOr, Response, ResponseBuilder,
+    },
+    OpenApi,
+};
+
+use crate::http::{
+    auth,
+    error::{self, Code},
+    users,
+};
+
+/// Generated once, the document only changes along with the code
+static DOCUMENT: Lazy<Document> = Lazy::new(document);
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "MindTrails",
+        description = "The API behind the MindTrails app"
+    ),
+    servers((url = "/v1")),
+    components(schemas(error::ErrorPayload, error::ProblemPayload, error::FieldError))
+)]
+struct Api;
+
+pub(in crate::http) fn router() -> Router
+{
+    Router::new().route("/openapi.json", get(fetch_document))
+}
+
+async fn fetch_document() -> Json<&'static Document>
+{
+    Json(&DOCUMENT)
+}
+
+/// The OpenAPI document of the routes that have been annotated so far, with
+/// the errors each operation can fail with
+fn document() -> Document
+{
+    let mut document = Api::openapi();
+    // Would otherwise be filled in from the empty `license` of the package
+    document.info.license = None;
+
+    let apis = [
+        (auth::Api::openapi(), auth::ERROR_CODES),
+        (users::Api::openapi(), users::ERROR_CODES),
+    ];
+    for (mut api, error_codes) in apis {
+        for (operation_id, codes) in error_codes {
+            let operation = operation(&mut api, operation_id).unwrap_or_else(|| {
+                panic!("no operation `{operation_id}` to document the error codes of")
+            });
+            add_error_responses(operation, codes);
+        }
+
+        document.merge(api);
+    }
+
+    document
+}
+
+fn operation<'a>(api: &'a mut Document, operation_id: &str) -> Option<&'a mut Operation>
+{
+    api.paths
+        .paths
+        .values_mut()
+        .flat_map(|path_item| path_item.operations.values_mut())
+        .find(|operation| operation.operation_id.as_deref() == Some(operation_id))
+}
+
+/// What any operation can fail with, whatever it does
+const COMMON_ERROR_CODES: &[Code] = &[
+    Code::REQUEST_TIMED_OUT,
+    Code::OVERLOADED,
+    Code::SHUTTING_DOWN,
+    Code::INTERNAL_SERVER_ERROR,
+];
+
+/// One response per status, which lists the codes that come with it
+fn add_error_responses(operation: &mut Operation, codes: &[Code])
+{
+    let mut by_status = BTreeMap::<u16, Vec<Code>>::new();
+    for code in codes.iter().chain(COMMON_ERROR_CODES) {
+        by_status
+            .entry(code.status().as_u16())
+            .or_default()
+            .push(*code);
+    }
+
+    for (status, codes) in by_status {
+        let description = codes
+            .iter()
+            .map(|code| format!("- `{}` {}: {}", code.code(), code.title(), code.message()))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let _prev_response = operation
+            .responses
+            .responses
+            .insert(status.to_string(), RefOr::T(error_response(description)));
+    }
+}
+
+fn error_response(description: String) -> Response
+{
+    ResponseBuilder::new()
+        .description(description)
+        .content(
+            "application/json",
+            Content::new(Ref::from_schema_name("ErrorPayload")),
+        )
+        .content(
+            error::PROBLEM_JSON,
+            Content::new(Ref::from_schema_name("ProblemPayload")),
+        )
+        .build()
+}
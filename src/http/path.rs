@@ -0,0 +1,39 @@
+pub(in crate::http) mod extractor
+{
+    use crate::http::{self, error};
+
+    use axum::extract::rejection;
+
+    use axum_macros::FromRequestParts;
+
+    #[derive(FromRequestParts)]
+    #[from_request(via(axum::extract::Path), rejection(http::Error))]
+    pub(in crate::http) struct Path<T>(pub(in crate::http) T);
+
+    impl From<rejection::PathRejection> for http::Error
+    {
+        fn from(rejection: rejection::PathRejection) -> Self
+        {
+            let error_code = match rejection {
+                rejection::PathRejection::FailedToDeserializePathParams(_) => {
+                    error::Code::INVALID_PATH_PARAMETER
+                }
+                _ => error::Code::INTERNAL_SERVER_ERROR,
+            };
+
+            let message = match rejection {
+                rejection::PathRejection::FailedToDeserializePathParams(_) => rejection.to_string(),
+                _ => {
+                    tracing::error!(error = %rejection, "unexpected path rejection");
+                    String::from(http::error::INTERNAL_SERVER_ERROR_MESSAGE)
+                }
+            };
+
+            http::Error {
+                error_code,
+                message,
+                errors: Vec::new(),
+            }
+        }
+    }
+}
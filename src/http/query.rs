@@ -0,0 +1,77 @@
+use thiserror::Error;
+
+use crate::http;
+
+/// How many rows come before `page`, counted from 1, refusing pages too far
+/// off for the offset to be represented
+pub(in crate::http) fn page_offset(page: i64, per_page: i64) -> Result<i64, http::Error>
+{
+    (page - 1)
+        .checked_mul(per_page)
+        .ok_or_else(|| Error::PageTooLarge.into())
+}
+
+#[derive(Debug, Error)]
+enum Error
+{
+    #[error("`page` is too large")]
+    PageTooLarge,
+}
+
+impl From<Error> for http::Error
+{
+    fn from(err: Error) -> Self
+    {
+        let error_code = match err {
+            Error::PageTooLarge => http::error::Code::INVALID_QUERY_PARAMETER,
+        };
+
+        http::Error {
+            error_code,
+            message: err.to_string(),
+            errors: Vec::new(),
+        }
+    }
+}
+
+pub(in crate::http) mod extractor
+{
+    use crate::http::{self, error};
+
+    use axum::extract::rejection;
+
+    use axum_macros::FromRequestParts;
+
+    #[derive(FromRequestParts)]
+    #[from_request(via(axum::extract::Query), rejection(http::Error))]
+    pub(in crate::http) struct Query<T>(pub(in crate::http) T);
+
+    impl From<rejection::QueryRejection> for http::Error
+    {
+        fn from(rejection: rejection::QueryRejection) -> Self
+        {
+            let error_code = match rejection {
+                rejection::QueryRejection::FailedToDeserializeQueryString(_) => {
+                    error::Code::INVALID_QUERY_PARAMETER
+                }
+                _ => error::Code::INTERNAL_SERVER_ERROR,
+            };
+
+            let message = match rejection {
+                rejection::QueryRejection::FailedToDeserializeQueryString(_) => {
+                    rejection.to_string()
+                }
+                _ => {
+                    tracing::error!(error = %rejection, "unexpected query rejection");
+                    String::from(http::error::INTERNAL_SERVER_ERROR_MESSAGE)
+                }
+            };
+
+            http::Error {
+                error_code,
+                message,
+                errors: Vec::new(),
+            }
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Instant,
+};
+
+use redis::AsyncCommands;
+
+use thiserror::Error;
+
+use crate::{
+    config,
+    http::{self, client_info::ClientInfo},
+};
+
+/// Counts an attempt at `KEYS[1]` and starts its window of `ARGV[1]` seconds
+/// on the first one, returning how many were made in the window
+const ATTEMPT_SCRIPT: &str = r"
+local attempts = redis.call('INCR', KEYS[1])
+if attempts == 1 then
+    redis.call('EXPIRE', KEYS[1], ARGV[1])
+end
+return attempts
+";
+
+/// Refuses logins for a username from a client once it made too many attempts
+/// at it within a window, so its password can't be guessed by brute force.
+///
+/// Attempts are counted per client as well, so failing on purpose doesn't
+/// lock the user out anywhere else, and the window is fixed, so it is over
+/// `login_window` after the first attempt whatever happens meanwhile.
+#[derive(Debug, Clone)]
+pub struct LoginLimiter
+{
+    backend: Backend,
+    config: config::Shared,
+}
+
+#[derive(Debug, Clone)]
+enum Backend
+{
+    Redis(redis::Client),
+    Memory(Arc<Mutex<HashMap<String, Attempts>>>),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Attempts
+{
+    count: u32,
+    window_ends_at: Instant,
+}
+
+fn login_attempts_key(username: &str, ip: Option<IpAddr>) -> String
+{
+    match ip {
+        Some(ip) => format!("login_attempts:{ip}:{username}"),
+        None => format!("login_attempts:unknown:{username}"),
+    }
+}
+
+impl LoginLimiter
+{
+    pub fn new(client: redis::Client, config: config::Shared) -> Self
+    {
+        LoginLimiter {
+            backend: Backend::Redis(client),
+            config,
+        }
+    }
+
+    /// A limiter counting attempts in this process only, for tests
+    pub fn in_memory(config: config::Shared) -> Self
+    {
+        LoginLimiter {
+            backend: Backend::Memory(Arc::new(Mutex::new(HashMap::new()))),
+            config,
+        }
+    }
+
+    /// Counts an attempt at logging in as `username` from `client`, refusing
+    /// it when there were too many already. Meant to be made before the
+    /// password is verified, and `reset` once it was right.
+    pub(in crate::http) async fn attempt(
+        &self,
+        username: &str,
+        client: &ClientInfo,
+    ) -> Result<(), self::Error>
+    {
+        let rate_limit = *self.config.load().rate_limit();
+        let key = login_attempts_key(username, client.ip);
+
+        let attempts = match &self.backend {
+            Backend::Redis(client) => {
+                let mut connection = connection(client).await?;
+
+                redis::Script::new(ATTEMPT_SCRIPT)
+                    .key(key)
+                    .arg(rate_limit.login_window().as_secs())
+                    .invoke_async::<_, u32>(&mut connection)
+                    .await?
+            }
+            Backend::Memory(memory) => {
+                let now = Instant::now();
+                let mut memory = lock(memory);
+                let attempts = memory.entry(key).or_insert(Attempts {
+                    count: 0,
+                    window_ends_at: now,
+                });
+                if attempts.window_ends_at <= now {
+                    *attempts = Attempts {
+                        count: 0,
+                        window_ends_at: now + rate_limit.login_window(),
+                    };
+                }
+                attempts.count += 1;
+
+                attempts.count
+            }
+        };
+
+        if attempts > rate_limit.login_attempts() {
+            Err(Error::TooManyAttempts)?;
+        }
+
+        Ok(())
+    }
+
+    pub(in crate::http) async fn reset(
+        &self,
+        username: &str,
+        client: &ClientInfo,
+    ) -> Result<(), self::Error>
+    {
+        let key = login_attempts_key(username, client.ip);
+
+        let client = match &self.backend {
+            Backend::Redis(client) => client,
+            Backend::Memory(memory) => {
+                let _attempts = lock(memory).remove(&key);
+
+                return Ok(());
+            }
+        };
+        let mut connection = connection(client).await?;
+
+        connection.del::<_, ()>(key).await?;
+
+        Ok(())
+    }
+}
+
+async fn connection(client: &redis::Client) -> Result<redis::aio::Connection, self::Error>
+{
+    client.get_tokio_connection().await.map_err(Error::from)
+}
+
+fn lock(memory: &Mutex<HashMap<String, Attempts>>) -> MutexGuard<'_, HashMap<String, Attempts>>
+{
+    // SAFETY: Nothing can panic while the lock is held, so it is never
+    // poisoned
+    memory.lock().unwrap()
+}
+
+#[derive(Debug, Error)]
+pub(in crate::http) enum Error
+{
+    #[error("{inner}")]
+    Redis
+    {
+        #[from]
+        inner: redis::RedisError,
+    },
+    #[error("too many failed login attempts, try again later")]
+    TooManyAttempts,
+}
+
+impl From<Error> for http::Error
+{
+    fn from(err: Error) -> Self
+    {
+        let error_code = match err {
+            Error::Redis { .. } => http::error::Code::INTERNAL_SERVER_ERROR,
+            Error::TooManyAttempts => http::error::Code::TOO_MANY_LOGIN_ATTEMPTS,
+        };
+
+        let message = match err {
+            Error::Redis { .. } => {
+                tracing::error!(error = %err, ?err, "rate limit error");
+                String::from(http::error::INTERNAL_SERVER_ERROR_MESSAGE)
+            }
+            Error::TooManyAttempts => err.to_string(),
+        };
+
+        http::Error {
+            error_code,
+            message,
+            errors: Vec::new(),
+        }
+    }
+}
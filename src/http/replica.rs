@@ -0,0 +1,55 @@
+use axum::{
+    headers::Cookie, http::Request, middleware::Next, response::Response, Extension, TypedHeader,
+};
+
+use crate::{config, db::DbPools, http};
+
+const PIN_COOKIE_NAME: &str = "mindtrails_pin_primary";
+
+/// Sends the reads of a client that just wrote something to the primary for
+/// `postgres.replica_pin_secs`, so they see their own writes even while the
+/// replica lags behind.
+///
+/// The pin is a cookie rather than server-side state, so it also covers
+/// clients without a session and doesn't cost a round trip to redis.
+pub(in crate::http) async fn pin_after_writes<B>(
+    db: Extension<DbPools>,
+    config: Extension<config::Shared>,
+    cookie: Option<TypedHeader<Cookie>>,
+    mut req: Request<B>,
+    next: Next<B>,
+) -> Response
+{
+    if db.replica().is_none() {
+        return next.run(req).await;
+    }
+
+    let pinned = cookie
+        .as_ref()
+        .and_then(|cookie| cookie.get(PIN_COOKIE_NAME))
+        .is_some();
+    if pinned {
+        let _prev_value = req.extensions_mut().insert(db.pinned_to_primary());
+    }
+
+    let writes = !req.method().is_safe();
+    let mut res = next.run(req).await;
+
+    if writes && res.status().is_success() {
+        let config = config.load();
+        let set_cookie = format!(
+            "{PIN_COOKIE_NAME}=1; Max-Age={}{}",
+            config.postgres_replica_pin().as_secs(),
+            config.cookie().attributes()
+        );
+        // SAFETY: The cookie's name and value are ASCII, and so are its
+        // attributes as the cookie domain is validated when the config is
+        // loaded
+        let header_value = http::HeaderValue::from_str(&set_cookie).unwrap();
+        let _appended = res
+            .headers_mut()
+            .append(http::header::SET_COOKIE, header_value);
+    }
+
+    res
+}
@@ -0,0 +1,65 @@
+use axum::{
+    http::{header::HeaderName, Request},
+    middleware::Next,
+    response,
+};
+use tower_http::request_id::RequestId;
+use tracing::Span;
+
+use crate::http::client_info::ClientInfo;
+
+pub(in crate::http) const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// The id of the request currently being handled, if any
+pub(in crate::http) fn current() -> Option<String>
+{
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Makes the request's id available to `current` for as long as the request
+/// is being handled, so errors can echo it back without every handler having
+/// to extract it
+pub(in crate::http) async fn scope<B>(req: Request<B>, next: Next<B>) -> response::Response
+{
+    let request_id = req
+        .extensions()
+        .get::<RequestId>()
+        .and_then(|request_id| request_id.header_value().to_str().ok())
+        .map(String::from);
+
+    match request_id {
+        Some(request_id) => REQUEST_ID.scope(request_id, next.run(req)).await,
+        None => next.run(req).await,
+    }
+}
+
+pub(in crate::http) fn make_span<B>(req: &Request<B>) -> Span
+{
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|request_id| request_id.to_str().ok())
+        .unwrap_or_default();
+
+    let span = tracing::info_span!(
+        "request",
+        method = %req.method(),
+        uri = %req.uri(),
+        request_id,
+        client_ip = tracing::field::Empty,
+    );
+    // So everything logged while handling the request says who it was for
+    if let Some(ip) = req
+        .extensions()
+        .get::<ClientInfo>()
+        .and_then(|client_info| client_info.ip)
+    {
+        let _span = span.record("client_ip", tracing::field::display(ip));
+    }
+
+    span
+}
@@ -0,0 +1,59 @@
+use axum::{extract::State, http::Request, middleware::Next, response};
+
+use crate::{
+    config,
+    http::{
+        client_info::{ClientInfo, Scheme},
+        header, HeaderValue,
+    },
+};
+
+/// Adds the headers telling browsers to only reach the API over HTTPS, not to
+/// guess content types, not to leak the URL to other sites and not to let
+/// other sites frame responses, as set in `security_headers`
+pub(in crate::http) async fn set<B>(
+    State(config): State<config::Shared>,
+    req: Request<B>,
+    next: Next<B>,
+) -> response::Response
+{
+    let scheme = req
+        .extensions()
+        .get::<ClientInfo>()
+        .map(|client_info| client_info.scheme);
+
+    let mut res = next.run(req).await;
+
+    let config = config.load();
+    let security_headers = config.security_headers();
+    let headers = res.headers_mut();
+
+    // Browsers ignore it over plain HTTP, where it could have been added by
+    // anyone in between
+    let strict_transport_security = security_headers
+        .strict_transport_security()
+        .filter(|_| scheme == Some(Scheme::Https));
+    if let Some(strict_transport_security) = strict_transport_security {
+        // SAFETY: Digits and ASCII letters only
+        let _prev_value = headers.insert(
+            header::STRICT_TRANSPORT_SECURITY,
+            HeaderValue::from_str(&strict_transport_security).unwrap(),
+        );
+    }
+    let _prev_value = headers.insert(
+        header::X_CONTENT_TYPE_OPTIONS,
+        HeaderValue::from_static("nosniff"),
+    );
+    let _prev_value = headers.insert(
+        header::REFERRER_POLICY,
+        HeaderValue::from_static(security_headers.referrer_policy().as_str()),
+    );
+    // SAFETY: `frame_ancestors` is checked to make a valid header value when
+    // the config is loaded
+    let _prev_value = headers.insert(
+        header::CONTENT_SECURITY_POLICY,
+        HeaderValue::from_str(&security_headers.content_security_policy()).unwrap(),
+    );
+
+    res
+}
@@ -6,12 +6,17 @@ use axum::{
 use async_trait::async_trait;
 use uuid::Uuid;
 
-use crate::http::{self, session};
+use crate::http::{self, locale, session};
 
 #[derive(Debug)]
 pub(in crate::http) enum UserId
 {
     Found(Uuid),
+    Impersonated
+    {
+        user_id: Uuid,
+        impersonator_id: Uuid,
+    },
     NotFound,
 }
 
@@ -44,11 +49,20 @@ where
         match session_cookie {
             Some(session_cookie) => {
                 let session = store.load_session(session_cookie).await?;
+                prefer_locale(&session).await;
+
+                let user_id = session.get::<Uuid>("user_id").await;
+                let impersonator_id = session
+                    .get::<Uuid>(session::IMPERSONATOR_ID_SESSION_KEY)
+                    .await;
 
-                if let Some(user_id) = session.get::<Uuid>("user_id").await {
-                    Ok(UserId::Found(user_id))
-                } else {
-                    Ok(UserId::NotFound)
+                match (user_id, impersonator_id) {
+                    (Some(user_id), Some(impersonator_id)) => Ok(UserId::Impersonated {
+                        user_id,
+                        impersonator_id,
+                    }),
+                    (Some(user_id), None) => Ok(UserId::Found(user_id)),
+                    (None, _) => Ok(UserId::NotFound),
                 }
             }
             None => Ok(UserId::NotFound),
@@ -92,6 +106,7 @@ where
         match session_cookie {
             Some(session_cookie) => {
                 let session = store.load_session(session_cookie).await?;
+                prefer_locale(&session).await;
 
                 Ok(Session::Found(session))
             }
@@ -99,3 +114,15 @@ where
         }
     }
 }
+
+/// Errors are rendered in the locale the user picked from here on, whichever
+/// extractor loads the session first
+async fn prefer_locale(session: &session::Session)
+{
+    if let Some(locale) = session
+        .get::<locale::Locale>(locale::LOCALE_SESSION_KEY)
+        .await
+    {
+        locale::prefer(locale);
+    }
+}
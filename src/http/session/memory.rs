@@ -0,0 +1,131 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+use uuid::Uuid;
+
+/// Sessions kept in the memory of this process, laid out like they are in
+/// redis: records by session id, plus the ids of every user's sessions.
+///
+/// Expired records are only dropped once they are overwritten or destroyed,
+/// until then they are treated as missing. Their ids are dropped from the
+/// user's index whenever another session of theirs is stored.
+#[derive(Debug, Default)]
+pub(in crate::http::session) struct Memory
+{
+    records: HashMap<String, Record>,
+    user_sessions: HashMap<Uuid, HashSet<String>>,
+}
+
+#[derive(Debug)]
+struct Record
+{
+    value: String,
+    expires_at: Option<Instant>,
+}
+
+impl Record
+{
+    fn expired(&self) -> bool
+    {
+        matches!(self.expires_at, Some(expires_at) if expires_at <= Instant::now())
+    }
+}
+
+impl Memory
+{
+    pub(in crate::http::session) fn get(&self, id: &str) -> Option<String>
+    {
+        self.records
+            .get(id)
+            .filter(|record| !record.expired())
+            .map(|record| record.value.clone())
+    }
+
+    /// `None` if there is no such record, `Some(None)` if it never expires
+    pub(in crate::http::session) fn ttl(&self, id: &str) -> Option<Option<Duration>>
+    {
+        self.records
+            .get(id)
+            .filter(|record| !record.expired())
+            .map(|record| {
+                record
+                    .expires_at
+                    .map(|expires_at| expires_at.saturating_duration_since(Instant::now()))
+            })
+    }
+
+    pub(in crate::http::session) fn set(
+        &mut self,
+        id: String,
+        value: String,
+        expires_in: Option<Duration>,
+        user_ids: &[Uuid],
+    )
+    {
+        for &user_id in user_ids {
+            let records = &self.records;
+            let ids = self.user_sessions.entry(user_id).or_default();
+            ids.retain(|id| records.get(id).is_some_and(|record| !record.expired()));
+            let _new_id = ids.insert(id.clone());
+        }
+
+        let record = Record {
+            value,
+            expires_at: expires_in.map(|expires_in| Instant::now() + expires_in),
+        };
+        let _prev_record = self.records.insert(id, record);
+    }
+
+    /// Replaces the value of a record that hasn't expired, returning whether
+    /// there was one
+    pub(in crate::http::session) fn update(&mut self, id: &str, value: String) -> bool
+    {
+        match self.records.get_mut(id) {
+            Some(record) if !record.expired() => {
+                record.value = value;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub(in crate::http::session) fn remove(&mut self, id: &str, user_ids: &[Uuid])
+    {
+        let _record = self.records.remove(id);
+
+        for user_id in user_ids {
+            if let Some(ids) = self.user_sessions.get_mut(user_id) {
+                let _removed = ids.remove(id);
+                if ids.is_empty() {
+                    let _ids = self.user_sessions.remove(user_id);
+                }
+            }
+        }
+    }
+
+    pub(in crate::http::session) fn user_session_ids(&self, user_id: Uuid) -> Vec<String>
+    {
+        self.user_sessions
+            .get(&user_id)
+            .map(|ids| ids.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Removes every session of the user, returning how many hadn't expired
+    pub(in crate::http::session) fn remove_user_sessions(&mut self, user_id: Uuid) -> usize
+    {
+        let ids = self.user_sessions.remove(&user_id).unwrap_or_default();
+
+        ids.iter()
+            .filter_map(|id| self.records.remove(id))
+            .filter(|record| !record.expired())
+            .count()
+    }
+
+    pub(in crate::http::session) fn user_ids(&self) -> Vec<Uuid>
+    {
+        self.user_sessions.keys().copied().collect()
+    }
+}
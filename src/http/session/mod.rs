@@ -12,12 +12,17 @@ use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use thiserror::Error;
 
 pub(in crate::http) mod extractor;
+mod memory;
 mod store;
 
 pub use store::Store;
 
 pub(in crate::http) const SESSION_COOKIE_NAME: &str = "mindtrails_session";
 
+/// Set on sessions an admin opened to see the app as another user, holding
+/// the admin's own user id
+pub(in crate::http) const IMPERSONATOR_ID_SESSION_KEY: &str = "impersonator_id";
+
 fn generate_cookie(len: usize) -> String
 {
     let mut key = vec![0; len];
@@ -67,7 +72,7 @@ impl Session
         Ok(base64::encode(hash.as_bytes()))
     }
 
-    async fn get<T>(&self, key: &str) -> Option<T>
+    pub(in crate::http) async fn get<T>(&self, key: &str) -> Option<T>
     where
         T: DeserializeOwned,
     {
@@ -123,7 +128,7 @@ impl Clone for Session
 }
 
 #[derive(Debug, Error)]
-pub(in crate::http) enum Error
+pub enum Error
 {
     #[error("{inner}")]
     Base64Decode
@@ -1,26 +1,157 @@
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
 use redis::AsyncCommands;
 
-use crate::http::session;
+use uuid::Uuid;
+
+use crate::{
+    config,
+    http::{
+        self,
+        session::{self, memory::Memory},
+    },
+    metrics,
+};
+
+const USER_SESSIONS_KEY_PREFIX: &str = "user_sessions:";
+
+/// Deletes every session in the index at `KEYS[1]` along with the index, in
+/// one go so a session stored meanwhile can't slip through. Returns how many
+/// sessions there were.
+const DESTROY_USER_SESSIONS_SCRIPT: &str = r"
+local destroyed = 0
+for _, id in ipairs(redis.call('SMEMBERS', KEYS[1])) do
+    destroyed = destroyed + redis.call('DEL', id)
+end
+redis.call('DEL', KEYS[1])
+return destroyed
+";
+
+/// Drops the ids of sessions that expired from the index at `KEYS[1]`, which
+/// redis can't do on its own
+const PRUNE_USER_SESSIONS_SCRIPT: &str = r"
+for _, id in ipairs(redis.call('SMEMBERS', KEYS[1])) do
+    if redis.call('EXISTS', id) == 0 then
+        redis.call('SREM', KEYS[1], id)
+    end
+end
+";
+
+fn user_sessions_key(user_id: Uuid) -> String
+{
+    format!("{USER_SESSIONS_KEY_PREFIX}{user_id}")
+}
+
+/// The users a session is indexed under, its user and the admin
+/// impersonating them if any, so revoking the sessions of either revokes it
+async fn indexed_user_ids(session: &session::Session) -> Vec<Uuid>
+{
+    let user_id = session.get::<Uuid>("user_id").await;
+    let impersonator_id = session
+        .get::<Uuid>(session::IMPERSONATOR_ID_SESSION_KEY)
+        .await;
+
+    user_id.into_iter().chain(impersonator_id).collect()
+}
+
+/// A stored session as listed by `Store::list_user_sessions`
+#[derive(Debug)]
+pub(crate) struct SessionSummary
+{
+    pub(crate) id: String,
+    pub(crate) expires_in: Option<Duration>,
+}
 
 #[derive(Debug, Clone)]
 pub struct Store
 {
-    client: redis::Client,
+    backend: Backend,
+    cookie: config::Cookie,
+}
+
+#[derive(Debug, Clone)]
+enum Backend
+{
+    Redis(redis::Client),
+    Memory(Arc<Mutex<Memory>>),
 }
 
 impl Store
 {
-    pub fn new(client: redis::Client) -> Self
+    pub fn new(client: redis::Client, cookie: config::Cookie) -> Self
+    {
+        Store {
+            backend: Backend::Redis(client),
+            cookie,
+        }
+    }
+
+    /// A store keeping sessions in this process only, so they don't survive
+    /// a restart and aren't shared with other instances, which is what tests
+    /// want
+    pub fn in_memory(cookie: config::Cookie) -> Self
+    {
+        Store {
+            backend: Backend::Memory(Arc::new(Mutex::new(Memory::default()))),
+            cookie,
+        }
+    }
+
+    /// Headers setting the session cookie to the value returned by
+    /// `Store::store_session`
+    pub(in crate::http) fn set_cookie_headers(&self, cookie: &str) -> http::HeaderMap
     {
-        Store { client }
+        self.cookie_headers(&format!(
+            "{}={}{}",
+            session::SESSION_COOKIE_NAME,
+            cookie,
+            self.cookie.attributes()
+        ))
     }
 
-    async fn connection(&self) -> Result<redis::aio::Connection, session::Error>
+    /// Headers telling the browser to drop the session cookie
+    pub(in crate::http) fn clear_cookie_headers(&self) -> http::HeaderMap
     {
-        self.client
-            .get_tokio_connection()
-            .await
-            .map_err(session::Error::from)
+        self.cookie_headers(&format!(
+            "{}={}; Max-Age=0",
+            session::SESSION_COOKIE_NAME,
+            self.cookie.attributes()
+        ))
+    }
+
+    fn cookie_headers(&self, set_cookie: &str) -> http::HeaderMap
+    {
+        let mut headers = http::HeaderMap::new();
+        let header_value = http::HeaderValue::from_str(set_cookie)
+            // SAFETY: It is known in advance that `SESSION_COOKIE_NAME` as
+            // well as the cookie propagated from the init of the session are
+            // both always going to be ASCII-only, and the cookie domain is
+            // validated when the config is loaded, therefore the formatted
+            // string will be ASCII-only, so creating the HeaderValue will
+            // never fail
+            .unwrap();
+        let _prev_value = headers.insert(http::header::SET_COOKIE, header_value);
+
+        headers
+    }
+
+    pub(in crate::http) async fn ping(&self) -> Result<(), session::Error>
+    {
+        match &self.backend {
+            Backend::Redis(client) => {
+                let mut connection = connection(client).await?;
+
+                redis::cmd("PING")
+                    .query_async::<_, ()>(&mut connection)
+                    .await?;
+            }
+            Backend::Memory(_memory) => {}
+        }
+
+        Ok(())
     }
 
     pub(in crate::http) async fn load_session(
@@ -28,15 +159,27 @@ impl Store
         cookie: &str,
     ) -> Result<session::Session, session::Error>
     {
+        let _timer = metrics::SESSION_STORE_DURATION
+            .with_label_values(&["load"])
+            .start_timer();
+
         let id = session::Session::id_from_cookie(cookie)?;
-        let mut connection = self.connection().await?;
 
-        let record = connection
-            .get::<_, Option<String>>(id)
-            .await
-            .map(|record| record.ok_or_else(|| session::Error::NoSessionFound))
-            .map_err(session::Error::from)
-            .flatten()?;
+        let record = match &self.backend {
+            Backend::Redis(client) => {
+                let mut connection = connection(client).await?;
+
+                connection
+                    .get::<_, Option<String>>(id)
+                    .await
+                    .map(|record| record.ok_or_else(|| session::Error::NoSessionFound))
+                    .map_err(session::Error::from)
+                    .flatten()?
+            }
+            Backend::Memory(memory) => lock(memory)
+                .get(&id)
+                .ok_or(session::Error::NoSessionFound)?,
+        };
 
         let session = serde_json::from_str(&record)?;
 
@@ -48,30 +191,242 @@ impl Store
         session: session::Session,
     ) -> Result<Option<String>, session::Error>
     {
+        let _timer = metrics::SESSION_STORE_DURATION
+            .with_label_values(&["store"])
+            .start_timer();
+
         let record = serde_json::to_string(&session)?;
-        let mut connection = self.connection().await?;
+        let user_ids = indexed_user_ids(&session).await;
 
-        match session.expires_in {
-            Some(expiry) => {
-                connection
-                    .set_ex(session.id.clone(), record, expiry.as_secs() as usize)
-                    .await?
+        let client = match &self.backend {
+            Backend::Redis(client) => client,
+            Backend::Memory(memory) => {
+                lock(memory).set(session.id.clone(), record, session.expires_in, &user_ids);
+
+                return Ok(session.into_cookie_value());
             }
-            None => connection.set(session.id.clone(), record).await?,
         };
+        let mut connection = connection(client).await?;
+
+        let mut pipe = redis::pipe();
+        let _pipe = match session.expires_in {
+            Some(expiry) => pipe
+                .atomic()
+                .set_ex(session.id.clone(), record, expiry.as_secs() as usize)
+                .ignore(),
+            None => pipe.atomic().set(session.id.clone(), record).ignore(),
+        };
+        // Sessions are indexed by user so all of them can be revoked at once
+        for &user_id in &user_ids {
+            let _pipe = pipe
+                .sadd(user_sessions_key(user_id), session.id.clone())
+                .ignore();
+        }
+        pipe.query_async::<_, ()>(&mut connection).await?;
+
+        for user_id in user_ids {
+            redis::Script::new(PRUNE_USER_SESSIONS_SCRIPT)
+                .key(user_sessions_key(user_id))
+                .invoke_async::<_, ()>(&mut connection)
+                .await?;
+        }
 
         Ok(session.into_cookie_value())
     }
 
+    /// Writes `session` back only if it's still stored, keeping its expiry
+    /// and leaving the user's index alone, so a session revoked meanwhile
+    /// isn't brought back. Returns whether it was still stored.
+    pub(in crate::http) async fn update_session(
+        &self,
+        session: &session::Session,
+    ) -> Result<bool, session::Error>
+    {
+        let _timer = metrics::SESSION_STORE_DURATION
+            .with_label_values(&["update"])
+            .start_timer();
+
+        let record = serde_json::to_string(session)?;
+
+        let client = match &self.backend {
+            Backend::Redis(client) => client,
+            Backend::Memory(memory) => return Ok(lock(memory).update(&session.id, record)),
+        };
+        let mut connection = connection(client).await?;
+
+        let updated = redis::cmd("SET")
+            .arg(&session.id)
+            .arg(record)
+            .arg("XX")
+            .arg("KEEPTTL")
+            .query_async::<_, Option<String>>(&mut connection)
+            .await?;
+
+        Ok(updated.is_some())
+    }
+
     pub(in crate::http) async fn destroy_session(
         &self,
         session: session::Session,
     ) -> Result<(), session::Error>
     {
-        let mut connection = self.connection().await?;
+        let _timer = metrics::SESSION_STORE_DURATION
+            .with_label_values(&["destroy"])
+            .start_timer();
+
+        let user_ids = indexed_user_ids(&session).await;
+
+        let client = match &self.backend {
+            Backend::Redis(client) => client,
+            Backend::Memory(memory) => {
+                lock(memory).remove(&session.id, &user_ids);
+
+                return Ok(());
+            }
+        };
+        let mut connection = connection(client).await?;
 
-        connection.del(session.id).await?;
+        let mut pipe = redis::pipe();
+        let _pipe = pipe.atomic().del(session.id.clone()).ignore();
+        for user_id in user_ids {
+            let _pipe = pipe
+                .srem(user_sessions_key(user_id), session.id.clone())
+                .ignore();
+        }
+        pipe.query_async::<_, ()>(&mut connection).await?;
 
         Ok(())
     }
+
+    /// The sessions belonging to the user that haven't expired yet
+    pub(crate) async fn list_user_sessions(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<SessionSummary>, session::Error>
+    {
+        let client = match &self.backend {
+            Backend::Redis(client) => client,
+            Backend::Memory(memory) => {
+                let memory = lock(memory);
+                let sessions = memory
+                    .user_session_ids(user_id)
+                    .into_iter()
+                    .filter_map(|id| {
+                        let expires_in = memory.ttl(&id)?;
+                        Some(SessionSummary { id, expires_in })
+                    })
+                    .collect();
+
+                return Ok(sessions);
+            }
+        };
+        let mut connection = connection(client).await?;
+
+        let key = user_sessions_key(user_id);
+        let ids = connection.smembers::<_, Vec<String>>(&key).await?;
+
+        let mut sessions = Vec::with_capacity(ids.len());
+        for id in ids {
+            // Redis reports sessions that expired as -2, which are dropped
+            // from the user's index then, while -1 means the session never
+            // expires
+            let expires_in = match connection.ttl::<_, i64>(&id).await? {
+                -2 => {
+                    let _removed = connection.srem::<_, _, i64>(&key, &id).await?;
+                    continue;
+                }
+                -1 => None,
+                secs => Some(Duration::from_secs(secs as u64)),
+            };
+            sessions.push(SessionSummary { id, expires_in });
+        }
+
+        Ok(sessions)
+    }
+
+    /// Destroys every session belonging to the user, returning how many
+    /// there were that hadn't expired
+    pub(crate) async fn destroy_user_sessions(
+        &self,
+        user_id: Uuid,
+    ) -> Result<usize, session::Error>
+    {
+        let _timer = metrics::SESSION_STORE_DURATION
+            .with_label_values(&["destroy_user"])
+            .start_timer();
+
+        let client = match &self.backend {
+            Backend::Redis(client) => client,
+            Backend::Memory(memory) => return Ok(lock(memory).remove_user_sessions(user_id)),
+        };
+
+        let mut connection = connection(client).await?;
+
+        let destroyed = redis::Script::new(DESTROY_USER_SESSIONS_SCRIPT)
+            .key(user_sessions_key(user_id))
+            .invoke_async::<_, usize>(&mut connection)
+            .await?;
+
+        Ok(destroyed)
+    }
+
+    /// Destroys the sessions of every user, returning how many each of them
+    /// had.
+    ///
+    /// Sessions that never logged in aren't indexed by user and are left
+    /// alone, there's nothing in them worth revoking.
+    pub(crate) async fn destroy_all_user_sessions(
+        &self,
+    ) -> Result<Vec<(Uuid, usize)>, session::Error>
+    {
+        let client = match &self.backend {
+            Backend::Redis(client) => client,
+            Backend::Memory(memory) => {
+                let mut memory = lock(memory);
+                let destroyed = memory
+                    .user_ids()
+                    .into_iter()
+                    .map(|user_id| (user_id, memory.remove_user_sessions(user_id)))
+                    .collect();
+
+                return Ok(destroyed);
+            }
+        };
+        let mut connection = connection(client).await?;
+
+        let mut keys = Vec::new();
+        let mut iter = connection
+            .scan_match::<_, String>(format!("{USER_SESSIONS_KEY_PREFIX}*"))
+            .await?;
+        while let Some(key) = iter.next_item().await {
+            keys.push(key);
+        }
+
+        let mut destroyed = Vec::with_capacity(keys.len());
+        for key in keys {
+            let user_id = key
+                .strip_prefix(USER_SESSIONS_KEY_PREFIX)
+                .and_then(|user_id| user_id.parse().ok());
+            if let Some(user_id) = user_id {
+                destroyed.push((user_id, self.destroy_user_sessions(user_id).await?));
+            }
+        }
+
+        Ok(destroyed)
+    }
+}
+
+async fn connection(client: &redis::Client) -> Result<redis::aio::Connection, session::Error>
+{
+    client
+        .get_tokio_connection()
+        .await
+        .map_err(session::Error::from)
+}
+
+fn lock(memory: &Mutex<Memory>) -> MutexGuard<'_, Memory>
+{
+    // SAFETY: Nothing can panic while the lock is held, so it is never
+    // poisoned
+    memory.lock().unwrap()
 }
@@ -0,0 +1,190 @@
+use std::{future::Future, panic, time::Duration};
+
+use axum::{
+    extract::State,
+    http::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use thiserror::Error;
+use tokio::time::{Instant, MissedTickBehavior};
+use tokio_util::{sync::CancellationToken, task::TaskTracker};
+
+use crate::{
+    config,
+    http::{self, Servers},
+};
+
+/// How often draining logs how many requests it still waits on
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+/// How long the requests cut off at the drain timeout get to send their
+/// response before their connections are dropped
+const CUT_OFF_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Where shutting down is at, shared by every listener and request
+#[derive(Debug, Clone, Default)]
+pub struct Shutdown
+{
+    /// Cancelled once shutting down starts, `/readyz` fails from then on
+    draining: CancellationToken,
+    /// Cancelled once the grace period is over, the listeners stop accepting
+    /// connections then
+    stopping: CancellationToken,
+    /// Cancelled at the drain timeout, giving up on the requests left
+    cut_off: CancellationToken,
+    requests: TaskTracker,
+}
+
+impl Shutdown
+{
+    pub fn new() -> Self
+    {
+        Shutdown::default()
+    }
+
+    pub(in crate::http) fn is_draining(&self) -> bool
+    {
+        self.draining.is_cancelled()
+    }
+
+    /// Resolves once the listeners should stop accepting connections, for
+    /// their graceful shutdown
+    pub(in crate::http) fn stopping(&self) -> impl Future<Output = ()> + Send
+    {
+        self.stopping.clone().cancelled_owned()
+    }
+
+    /// Fails `/readyz`, stops accepting connections once the grace period is
+    /// over and waits for `servers` and the requests they are handling to
+    /// finish, cutting off whatever is left at the drain timeout
+    pub(in crate::http) async fn drain(&self, config: &config::Shutdown, servers: &mut Servers)
+    {
+        tracing::info!(
+            grace_period_secs = config.grace_period().as_secs(),
+            "shutting down, no longer ready"
+        );
+        self.draining.cancel();
+        tokio::time::sleep(config.grace_period()).await;
+
+        tracing::info!(
+            in_flight = self.requests.len(),
+            drain_timeout_secs = config.drain_timeout().as_secs(),
+            "no longer accepting connections, draining"
+        );
+        self.stopping.cancel();
+        let _closed = self.requests.close();
+
+        let deadline = Instant::now() + config.drain_timeout();
+        if self.wait(servers, deadline, true).await {
+            tracing::info!("drained");
+            return;
+        }
+
+        tracing::warn!(
+            in_flight = self.requests.len(),
+            "drain timed out, cutting off the requests left"
+        );
+        self.cut_off.cancel();
+        if !self
+            .wait(servers, Instant::now() + CUT_OFF_TIMEOUT, false)
+            .await
+        {
+            tracing::warn!(
+                in_flight = self.requests.len(),
+                "dropping connections still open"
+            );
+            servers.abort_all();
+        }
+    }
+
+    /// Waits for `servers` and the requests they are handling until
+    /// `deadline`, returning whether they all finished
+    async fn wait(&self, servers: &mut Servers, deadline: Instant, log_progress: bool) -> bool
+    {
+        let finished = async {
+            while let Some(server) = servers.join_next().await {
+                let served = server.unwrap_or_else(|err| panic::resume_unwind(err.into_panic()));
+                if let Err(err) = served {
+                    tracing::error!(error = %err, "server failed while draining");
+                }
+            }
+            self.requests.wait().await;
+        };
+        tokio::pin!(finished);
+
+        let mut progress =
+            tokio::time::interval_at(Instant::now() + PROGRESS_INTERVAL, PROGRESS_INTERVAL);
+        progress.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                () = &mut finished => return true,
+                () = tokio::time::sleep_until(deadline) => return false,
+                _instant = progress.tick(), if log_progress => {
+                    tracing::info!(in_flight = self.requests.len(), "draining");
+                }
+            }
+        }
+    }
+}
+
+/// Handles each request in a task of its own, so the session writes and audit
+/// events it makes are seen through even when the client goes away midway,
+/// and so shutting down can wait for it.
+///
+/// Meant to be the outermost layer, the task locals the other layers set up
+/// are only seen within the task.
+pub(in crate::http) async fn track<B>(
+    State(shutdown): State<Shutdown>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response
+where
+    B: Send + 'static,
+{
+    shutdown
+        .requests
+        .spawn(next.run(req))
+        .await
+        .unwrap_or_else(|err| panic::resume_unwind(err.into_panic()))
+}
+
+/// Gives up on the request once draining timed out, so it can still be told
+/// why before its connection is dropped
+pub(in crate::http) async fn cut_off<B>(
+    shutdown: Extension<Shutdown>,
+    req: Request<B>,
+    next: Next<B>,
+) -> Response
+{
+    tokio::select! {
+        res = next.run(req) => res,
+        () = shutdown.cut_off.cancelled() => http::Error::from(Error::CutOff).into_response(),
+    }
+}
+
+#[derive(Debug, Error)]
+enum Error
+{
+    #[error("the server shut down before the request could be handled, try again")]
+    CutOff,
+}
+
+impl From<Error> for http::Error
+{
+    fn from(err: Error) -> Self
+    {
+        let error_code = match err {
+            Error::CutOff => http::error::Code::SHUTTING_DOWN,
+        };
+
+        tracing::warn!(error = %err, "request cut off");
+
+        http::Error {
+            error_code,
+            message: err.to_string(),
+            errors: Vec::new(),
+        }
+    }
+}
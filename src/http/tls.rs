@@ -0,0 +1,397 @@
+use std::{
+    convert::Infallible,
+    fs::File,
+    io::{self, BufReader},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{Arc, PoisonError, RwLock, Weak},
+    task::{Context, Poll},
+    time::{Duration, SystemTime},
+};
+
+use axum::{
+    body::Body,
+    extract::{connect_info::Connected, State},
+    http::{uri::Authority, Request},
+    response::{IntoResponse, Redirect, Response},
+    Router,
+};
+use hyper::server::accept::Accept;
+use thiserror::Error;
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+    time::MissedTickBehavior,
+};
+use tokio_rustls::{
+    rustls::{
+        server::{ClientHello, ResolvesServerCert},
+        sign::{self, CertifiedKey},
+        Certificate, PrivateKey, ServerConfig,
+    },
+    server::TlsStream,
+    TlsAcceptor,
+};
+
+use crate::{
+    config,
+    http::{header, StatusCode, ACCEPT_ERROR_BACKOFF},
+};
+
+/// How long a client gets to finish the handshake before it's dropped, so
+/// half open connections don't pile up
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Handshaken connections waiting for hyper to pick them up
+const ACCEPTED_BACKLOG: usize = 64;
+
+/// The TLS connections made to a listener, as hyper serves them.
+///
+/// Handshakes happen in tasks of their own, so a slow client can't hold up
+/// the ones behind it.
+pub(in crate::http) struct Incoming
+{
+    connections: mpsc::Receiver<Connection>,
+}
+
+/// A connection that went through the handshake
+pub(in crate::http) struct Connection
+{
+    stream: TlsStream<TcpStream>,
+    remote_addr: SocketAddr,
+}
+
+/// The certificate handed to every client, swapped out whenever its files
+/// change
+struct Certificates
+{
+    current: RwLock<Arc<CertifiedKey>>,
+}
+
+impl Incoming
+{
+    /// Starts accepting connections on `listener`, with the certificate
+    /// `config` points to and advertising HTTP/2 as well as HTTP/1.1
+    pub(in crate::http) fn new(listener: TcpListener, config: &config::Tls) -> Result<Self, Error>
+    {
+        let modified = modified(config);
+        let certificates = Arc::new(Certificates {
+            current: RwLock::new(Arc::new(load(config)?)),
+        });
+
+        let mut server_config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_cert_resolver(certificates.clone());
+        server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+        let (sender, connections) = mpsc::channel(ACCEPTED_BACKLOG);
+
+        let _watch = tokio::spawn(watch(
+            Arc::downgrade(&certificates),
+            config.clone(),
+            modified,
+        ));
+        let _accept = tokio::spawn(accept(
+            listener,
+            TlsAcceptor::from(Arc::new(server_config)),
+            sender,
+        ));
+
+        Ok(Incoming { connections })
+    }
+}
+
+impl Accept for Incoming
+{
+    type Conn = Connection;
+    type Error = Infallible;
+
+    fn poll_accept(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>>
+    {
+        self.connections
+            .poll_recv(cx)
+            .map(|connection| connection.map(Ok))
+    }
+}
+
+/// Accepts connections until `Incoming` is dropped
+async fn accept(
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+    connections: mpsc::Sender<Connection>,
+)
+{
+    loop {
+        let accepted = tokio::select! {
+            accepted = listener.accept() => accepted,
+            () = connections.closed() => return,
+        };
+
+        let (stream, remote_addr) = match accepted {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                tracing::error!(error = %err, "failed to accept connection");
+                tokio::time::sleep(ACCEPT_ERROR_BACKOFF).await;
+                continue;
+            }
+        };
+
+        let acceptor = acceptor.clone();
+        let connections = connections.clone();
+        let _handshake = tokio::spawn(async move {
+            match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                Ok(Ok(stream)) => {
+                    // Only fails once hyper stopped accepting, the connection
+                    // is closed by dropping it then
+                    let _sent = connections
+                        .send(Connection {
+                            stream,
+                            remote_addr,
+                        })
+                        .await;
+                }
+                Ok(Err(err)) => tracing::debug!(%remote_addr, error = %err, "TLS handshake failed"),
+                Err(_elapsed) => tracing::debug!(%remote_addr, "TLS handshake timed out"),
+            }
+        });
+    }
+}
+
+/// Reloads the certificate whenever its files change, until `Incoming` is
+/// dropped. A certificate that fails to load is logged and the current one
+/// kept.
+async fn watch(
+    certificates: Weak<Certificates>,
+    config: config::Tls,
+    mut loaded: (Option<SystemTime>, Option<SystemTime>),
+)
+{
+    let mut interval = tokio::time::interval(config.reload_interval());
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    let mut seen = loaded;
+    loop {
+        let _instant = interval.tick().await;
+
+        let certificates = match certificates.upgrade() {
+            Some(certificates) => certificates,
+            None => return,
+        };
+
+        // Waiting for the files to stay the same for a whole interval keeps
+        // from loading a new certificate along with the old key while they
+        // are being replaced
+        let current = modified(&config);
+        if current != loaded && current == seen {
+            match load(&config) {
+                Ok(certified_key) => {
+                    *certificates
+                        .current
+                        .write()
+                        .unwrap_or_else(PoisonError::into_inner) = Arc::new(certified_key);
+                    tracing::info!("reloaded TLS certificate");
+                }
+                Err(err) => {
+                    tracing::error!(error = %err, "failed to reload TLS certificate");
+                }
+            }
+
+            loaded = current;
+        }
+
+        seen = current;
+    }
+}
+
+/// When the certificate and key files were last modified, `None` for those
+/// that can't be read
+fn modified(config: &config::Tls) -> (Option<SystemTime>, Option<SystemTime>)
+{
+    let modified = |path: &Path| {
+        path.metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    };
+
+    (modified(config.cert_path()), modified(config.key_path()))
+}
+
+fn load(config: &config::Tls) -> Result<CertifiedKey, Error>
+{
+    let cert_path = config.cert_path();
+    let certs = rustls_pemfile::certs(&mut open(cert_path)?).map_err(|inner| Error::Read {
+        path: cert_path.to_owned(),
+        inner,
+    })?;
+    if certs.is_empty() {
+        return Err(Error::NoCertificate {
+            path: cert_path.to_owned(),
+        });
+    }
+
+    let key_path = config.key_path();
+    let key = rustls_pemfile::read_all(&mut open(key_path)?)
+        .map_err(|inner| Error::Read {
+            path: key_path.to_owned(),
+            inner,
+        })?
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| Error::NoPrivateKey {
+            path: key_path.to_owned(),
+        })?;
+    let key = sign::any_supported_type(&key).map_err(|_| Error::UnsupportedPrivateKey {
+        path: key_path.to_owned(),
+    })?;
+
+    Ok(CertifiedKey::new(
+        certs.into_iter().map(Certificate).collect(),
+        key,
+    ))
+}
+
+fn open(path: &Path) -> Result<BufReader<File>, Error>
+{
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|inner| Error::Read {
+            path: path.to_owned(),
+            inner,
+        })
+}
+
+impl ResolvesServerCert for Certificates
+{
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>>
+    {
+        let current = self.current.read().unwrap_or_else(PoisonError::into_inner);
+        Some(current.clone())
+    }
+}
+
+/// Sends clients that came over plain HTTP to the same place over HTTPS on
+/// `https_port`
+pub(in crate::http) fn redirect_app(https_port: u16) -> Router
+{
+    Router::new().fallback(redirect).with_state(https_port)
+}
+
+async fn redirect(State(https_port): State<u16>, req: Request<Body>) -> Response
+{
+    let authority = req
+        .headers()
+        .get(header::HOST)
+        .and_then(|host| host.to_str().ok())
+        .and_then(|host| host.parse::<Authority>().ok())
+        .or_else(|| req.uri().authority().cloned());
+    let host = match &authority {
+        Some(authority) => authority.host(),
+        None => return StatusCode::BAD_REQUEST.into_response(),
+    };
+
+    let path_and_query = req
+        .uri()
+        .path_and_query()
+        .map_or("/", |path_and_query| path_and_query.as_str());
+
+    let location = if https_port == 443 {
+        format!("https://{host}{path_and_query}")
+    } else {
+        format!("https://{host}:{https_port}{path_and_query}")
+    };
+
+    Redirect::permanent(&location).into_response()
+}
+
+impl Connected<&Connection> for SocketAddr
+{
+    fn connect_info(target: &Connection) -> Self
+    {
+        target.remote_addr
+    }
+}
+
+impl AsyncRead for Connection
+{
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>>
+    {
+        Pin::new(&mut self.stream).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for Connection
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>>
+    {
+        Pin::new(&mut self.stream).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>>
+    {
+        Pin::new(&mut self.stream).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool
+    {
+        self.stream.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>>
+    {
+        Pin::new(&mut self.stream).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>>
+    {
+        Pin::new(&mut self.stream).poll_shutdown(cx)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum Error
+{
+    #[error("could not read {}: {inner}", .path.display())]
+    Read
+    {
+        path: PathBuf,
+        #[source]
+        inner: io::Error,
+    },
+    #[error("no certificate found in {}", .path.display())]
+    NoCertificate
+    {
+        path: PathBuf,
+    },
+    #[error("no private key found in {}", .path.display())]
+    NoPrivateKey
+    {
+        path: PathBuf,
+    },
+    #[error("unsupported private key in {}", .path.display())]
+    UnsupportedPrivateKey
+    {
+        path: PathBuf,
+    },
+}
@@ -0,0 +1,75 @@
+use std::{
+    convert::Infallible,
+    fs,
+    future::Future,
+    io,
+    os::unix::{fs::FileTypeExt, net},
+    path::Path,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+use hyper::server::accept::Accept;
+use tokio::{
+    net::{UnixListener, UnixStream},
+    time::Sleep,
+};
+
+use crate::http::ACCEPT_ERROR_BACKOFF;
+
+/// The connections made to a Unix domain socket, as hyper serves them
+pub(in crate::http) struct Incoming
+{
+    listener: UnixListener,
+    backoff: Option<Pin<Box<Sleep>>>,
+}
+
+impl Incoming
+{
+    /// Listens on `path`, replacing the socket a previous run left behind
+    /// unless something still listens on it
+    pub(in crate::http) fn bind(path: &Path) -> io::Result<Self>
+    {
+        match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.file_type().is_socket() => {
+                if net::UnixStream::connect(path).is_ok() {
+                    return Err(io::ErrorKind::AddrInUse.into());
+                }
+                fs::remove_file(path)?;
+            }
+            _ => {}
+        }
+
+        Ok(Incoming {
+            listener: UnixListener::bind(path)?,
+            backoff: None,
+        })
+    }
+}
+
+impl Accept for Incoming
+{
+    type Conn = UnixStream;
+    type Error = Infallible;
+
+    fn poll_accept(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>>
+    {
+        loop {
+            if let Some(backoff) = &mut self.backoff {
+                ready!(backoff.as_mut().poll(cx));
+                self.backoff = None;
+            }
+
+            match ready!(self.listener.poll_accept(cx)) {
+                Ok((stream, _addr)) => return Poll::Ready(Some(Ok(stream))),
+                Err(err) => {
+                    tracing::error!(error = %err, "failed to accept connection");
+                    self.backoff = Some(Box::pin(tokio::time::sleep(ACCEPT_ERROR_BACKOFF)));
+                }
+            }
+        }
+    }
+}
@@ -1,54 +1,437 @@
-use axum::{routing::post, Extension, Router};
-use sqlx::PgPool;
+use axum::{
+    extract::DefaultBodyLimit,
+    http::HeaderName,
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Extension, Json, Router,
+};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use utoipa::{OpenApi, ToSchema};
+use uuid::Uuid;
 
 use crate::{
-    http::{self, json},
-    password,
+    audit::{self, AuditEvent},
+    config,
+    db::DbPools,
+    http::{
+        self,
+        authz::{self, permission, RequirePermission},
+        client_info::ClientInfo,
+        error::Code,
+        json, locale, path, rate_limit, session,
+    },
+    password::{self, breach},
 };
 
-pub(in crate::http) fn router() -> Router
+/// Sent along with the `204` when a breached password was accepted anyway,
+/// under `breach::Policy::Warn`, with the number of breaches it appears in so
+/// the client can nudge the user into changing it
+pub(in crate::http) const PASSWORD_BREACHED_HEADER: HeaderName =
+    HeaderName::from_static("x-password-breached");
+
+pub(in crate::http) fn router(limits: &config::Limits) -> Router
+{
+    // Signing up is open to anyone, so these don't read more than credentials
+    let credentials = Router::new()
+        .route("/users", post(create_user))
+        .route("/users/password", post(change_password))
+        .route_layer(DefaultBodyLimit::max(limits.credentials_body()));
+
+    Router::new()
+        .merge(credentials)
+        .route("/users/locale", put(set_locale))
+        .route("/users/:user_id", get(fetch_user))
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    paths(create_user, change_password, set_locale, fetch_user),
+    components(schemas(User, CreateUser, ChangePassword, SetLocale, locale::Locale))
+)]
+pub(in crate::http) struct Api;
+
+/// What each operation of `Api` can fail with, besides the codes any
+/// operation can fail with
+pub(in crate::http) const ERROR_CODES: &[(&str, &[Code])] = &[
+    (
+        "create_user",
+        &[
+            Code::JSON_SYNTAX_ERROR,
+            Code::JSON_DATA_ERROR,
+            Code::JSON_MISSING_CONTENT_TYPE,
+            Code::PAYLOAD_TOO_LARGE,
+            Code::CROSS_SITE_REQUEST,
+            Code::USERNAME_TAKEN,
+            Code::PASSWORD_BREACHED,
+            Code::REGISTRATION_DISABLED,
+        ],
+    ),
+    (
+        "change_password",
+        &[
+            Code::JSON_SYNTAX_ERROR,
+            Code::JSON_DATA_ERROR,
+            Code::JSON_MISSING_CONTENT_TYPE,
+            Code::PAYLOAD_TOO_LARGE,
+            Code::CROSS_SITE_REQUEST,
+            Code::USER_NOT_FOUND,
+            Code::WRONG_PASSWORD,
+            Code::ACCOUNT_DISABLED,
+            Code::TOO_MANY_LOGIN_ATTEMPTS,
+            Code::PASSWORD_BREACHED,
+        ],
+    ),
+    (
+        "set_locale",
+        &[
+            Code::JSON_SYNTAX_ERROR,
+            Code::JSON_DATA_ERROR,
+            Code::JSON_MISSING_CONTENT_TYPE,
+            Code::PAYLOAD_TOO_LARGE,
+            Code::CROSS_SITE_REQUEST,
+            Code::NO_SESSION_FOUND,
+            Code::MUST_BE_AUTHENTICATED,
+            Code::FORBIDDEN_WHILE_IMPERSONATING,
+        ],
+    ),
+    (
+        "fetch_user",
+        &[
+            Code::NO_SESSION_FOUND,
+            Code::INVALID_PATH_PARAMETER,
+            Code::USER_NOT_FOUND,
+            Code::MUST_BE_AUTHENTICATED,
+            Code::FORBIDDEN,
+        ],
+    ),
+];
+
+#[derive(Serialize, ToSchema)]
+struct User
+{
+    user_id: Uuid,
+    username: String,
+    roles: Vec<String>,
+}
+
+/// A user along with the roles they hold
+#[utoipa::path(
+    get,
+    path = "/users/{user_id}",
+    tag = "users",
+    params(("user_id" = Uuid, Path, description = "The user's id")),
+    responses((status = 200, description = "The user", body = User))
+)]
+async fn fetch_user(
+    _permission: RequirePermission<permission::UsersRead>,
+    db: Extension<DbPools>,
+    path::extractor::Path(user_id): path::extractor::Path<Uuid>,
+) -> Result<Json<User>, http::Error>
 {
-    Router::new().route("/users", post(create_user))
+    let username = sqlx::query_scalar!(r#"select username from users where user_id = $1"#, user_id)
+        .fetch_optional(db.reader())
+        .await?
+        .ok_or(Error::UserNotFound)?;
+
+    // Only `None` if the user was deleted since the query above, in which
+    // case reporting no roles is still accurate
+    let roles = authz::Grants::load(db.reader(), user_id)
+        .await?
+        .map(|grants| grants.roles)
+        .unwrap_or_default();
+
+    Ok(Json(User {
+        user_id,
+        username,
+        roles,
+    }))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 struct CreateUser
 {
     username: String,
     password: String,
 }
 
+/// Registers a new user
+#[utoipa::path(
+    post,
+    path = "/users",
+    tag = "users",
+    request_body = CreateUser,
+    responses((
+        status = 204,
+        description = "Registered",
+        headers((
+            "x-password-breached" = u64,
+            description = "How many known data breaches the password appears in, if any"
+        ))
+    ))
+)]
 async fn create_user(
-    pg_pool: Extension<PgPool>,
+    config: Extension<config::Shared>,
+    db: Extension<DbPools>,
+    audit_log: Extension<audit::Log>,
+    client: ClientInfo,
+    password_hasher: Extension<password::Hasher>,
+    breach_checker: Extension<breach::Checker>,
     json::extractor::Json(req): json::extractor::Json<CreateUser>,
-) -> Result<http::StatusCode, http::Error>
+) -> Result<Response, http::Error>
 {
+    if !config.load().registration_enabled() {
+        Err(Error::RegistrationDisabled)?;
+    }
+
     let CreateUser { username, password } = req;
 
-    let password = password::hash(password).await?;
+    let breach_verdict = check_breached(&breach_checker, &password, "password").await?;
 
-    let pg_query_res = sqlx::query!(
+    let password = password_hasher.hash(password).await?;
+
+    let mut transaction = db.writer().begin().await?;
+
+    let pg_query_res = sqlx::query_scalar!(
         r#"
             INSERT INTO "users"(username, password)
             values ($1, $2)
+            RETURNING user_id
         "#,
         username,
         password
     )
-    .execute(&*pg_pool)
+    .fetch_one(&mut transaction)
     .await;
 
-    match pg_query_res {
-        Ok(_) => Ok(http::StatusCode::NO_CONTENT),
+    let user_id = match pg_query_res {
+        Ok(user_id) => user_id,
         Err(sqlx::Error::Database(database_err))
             if database_err.constraint() == Some("users_username_key") =>
         {
             Err(Error::UsernameTaken)?
         }
         Err(err) => Err(err)?,
+    };
+
+    audit_log
+        .record(
+            &mut transaction,
+            AuditEvent::UserCreated { user_id },
+            Some(&client.audit()),
+        )
+        .await?;
+    transaction.commit().await?;
+
+    Ok(breach_response(breach_verdict))
+}
+
+#[derive(Deserialize, ToSchema)]
+struct ChangePassword
+{
+    username: String,
+    password: String,
+    new_password: String,
+}
+
+/// Replaces the user's password, logging them out everywhere
+#[utoipa::path(
+    post,
+    path = "/users/password",
+    tag = "users",
+    request_body = ChangePassword,
+    responses((
+        status = 204,
+        description = "Changed",
+        headers((
+            "x-password-breached" = u64,
+            description = "How many known data breaches the new password appears in, if any"
+        ))
+    ))
+)]
+// Every argument is an extractor, grouping them would only hide what the
+// handler relies on
+#[allow(clippy::too_many_arguments)]
+async fn change_password(
+    db: Extension<DbPools>,
+    audit_log: Extension<audit::Log>,
+    client: ClientInfo,
+    session_store: Extension<session::Store>,
+    login_limiter: Extension<rate_limit::LoginLimiter>,
+    password_hasher: Extension<password::Hasher>,
+    breach_checker: Extension<breach::Checker>,
+    json::extractor::Json(req): json::extractor::Json<ChangePassword>,
+) -> Result<Response, http::Error>
+{
+    let ChangePassword {
+        username,
+        password,
+        new_password,
+    } = req;
+
+    // Otherwise this would be a way around the limit on guessing passwords
+    // by logging in
+    login_limiter.attempt(&username, &client).await?;
+
+    let user = sqlx::query!(
+        r#"select user_id, password, disabled from users where username = $1"#,
+        username
+    )
+    .fetch_optional(db.writer())
+    .await?
+    .ok_or(Error::UserNotFound)?;
+
+    if !password::verify(password, user.password).await? {
+        audit_log
+            .record(
+                db.writer(),
+                AuditEvent::LoginFailed {
+                    user_id: user.user_id,
+                },
+                Some(&client.audit()),
+            )
+            .await?;
+
+        Err(Error::WrongPassword)?;
+    }
+    login_limiter.reset(&username, &client).await?;
+    if user.disabled {
+        Err(Error::AccountDisabled)?;
+    }
+
+    let breach_verdict = check_breached(&breach_checker, &new_password, "new_password").await?;
+
+    let new_password = password_hasher.hash(new_password).await?;
+
+    let mut transaction = db.writer().begin().await?;
+
+    let _pg_query_res = sqlx::query!(
+        r#"
+            UPDATE "users" SET password = $2, password_reset_required = false
+            WHERE user_id = $1
+        "#,
+        user.user_id,
+        new_password
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    audit_log
+        .record(
+            &mut transaction,
+            AuditEvent::PasswordChanged {
+                user_id: user.user_id,
+            },
+            Some(&client.audit()),
+        )
+        .await?;
+    transaction.commit().await?;
+
+    // Sessions opened with the old password shouldn't outlive it
+    let _destroyed = session_store.destroy_user_sessions(user.user_id).await?;
+
+    Ok(breach_response(breach_verdict))
+}
+
+#[derive(Deserialize, ToSchema)]
+struct SetLocale
+{
+    /// `None` goes back to negotiating it from `Accept-Language`
+    locale: Option<locale::Locale>,
+}
+
+/// Sets the locale errors are rendered in for the logged in user
+///
+/// Their other sessions pick it up the next time they log in.
+#[utoipa::path(
+    put,
+    path = "/users/locale",
+    tag = "users",
+    request_body = SetLocale,
+    responses((status = 204, description = "Set"))
+)]
+async fn set_locale(
+    db: Extension<DbPools>,
+    session_store: Extension<session::Store>,
+    session: session::extractor::Session,
+    json::extractor::Json(req): json::extractor::Json<SetLocale>,
+) -> Result<http::StatusCode, http::Error>
+{
+    let mut session = match session {
+        session::extractor::Session::Found(session) => session,
+        session::extractor::Session::NotFound => Err(Error::MustBeAuthenticated)?,
+    };
+
+    // It's the admin reading the errors while impersonating, not the user
+    if session
+        .get::<Uuid>(session::IMPERSONATOR_ID_SESSION_KEY)
+        .await
+        .is_some()
+    {
+        Err(Error::ForbiddenWhileImpersonating)?;
+    }
+    let user_id = session
+        .get::<Uuid>("user_id")
+        .await
+        .ok_or(Error::MustBeAuthenticated)?;
+
+    // A `null` doesn't deserialize into a locale, so clearing it this way
+    // has the session fall back to `Accept-Language` too
+    session
+        .insert(locale::LOCALE_SESSION_KEY, req.locale)
+        .await?;
+    // The session may have been revoked since it was loaded, in which case
+    // the locale isn't saved either
+    if !session_store.update_session(&session).await? {
+        Err(Error::MustBeAuthenticated)?;
+    }
+
+    let _pg_query_res = sqlx::query!(
+        r#"UPDATE "users" SET locale = $2 WHERE user_id = $1"#,
+        user_id,
+        req.locale.map(locale::Locale::tag)
+    )
+    .execute(db.writer())
+    .await?;
+
+    Ok(http::StatusCode::NO_CONTENT)
+}
+
+/// Checks `password`, sent in the request body's `field`, against the
+/// breached passwords
+async fn check_breached(
+    breach_checker: &breach::Checker,
+    password: &str,
+    field: &'static str,
+) -> Result<breach::Verdict, http::Error>
+{
+    let breach_verdict = breach_checker
+        .check(password)
+        .await
+        .map_err(password::Error::from)?;
+
+    match breach_verdict {
+        breach::Verdict::Breached {
+            policy: breach::Policy::Reject,
+            ..
+        } => Err(Error::PasswordBreached { field })?,
+        _ => Ok(breach_verdict),
+    }
+}
+
+/// Under `breach::Policy::Warn` a breached password is still accepted, but
+/// the client is told in a header, leaving the response as it would be
+/// otherwise
+fn breach_response(breach_verdict: breach::Verdict) -> Response
+{
+    match breach_verdict {
+        breach::Verdict::Breached { occurrences, .. } => (
+            http::StatusCode::NO_CONTENT,
+            [(PASSWORD_BREACHED_HEADER, occurrences.to_string())],
+        )
+            .into_response(),
+        breach::Verdict::NotBreached => http::StatusCode::NO_CONTENT.into_response(),
     }
 }
 
@@ -57,6 +440,23 @@ enum Error
 {
     #[error("username already taken")]
     UsernameTaken,
+    #[error("password appears in a known data breach")]
+    PasswordBreached
+    {
+        field: &'static str,
+    },
+    #[error("registration of new users is disabled")]
+    RegistrationDisabled,
+    #[error("no such user was found")]
+    UserNotFound,
+    #[error("the provided password is wrong")]
+    WrongPassword,
+    #[error("this account has been disabled")]
+    AccountDisabled,
+    #[error("must be authenticated")]
+    MustBeAuthenticated,
+    #[error("not allowed while impersonating another user")]
+    ForbiddenWhileImpersonating,
 }
 
 impl From<Error> for http::Error
@@ -65,18 +465,38 @@ impl From<Error> for http::Error
     {
         let error_code = match err {
             Error::UsernameTaken => http::error::Code::USERNAME_TAKEN,
+            Error::PasswordBreached { .. } => http::error::Code::PASSWORD_BREACHED,
+            Error::RegistrationDisabled => http::error::Code::REGISTRATION_DISABLED,
+            Error::UserNotFound => http::error::Code::USER_NOT_FOUND,
+            Error::WrongPassword => http::error::Code::WRONG_PASSWORD,
+            Error::AccountDisabled => http::error::Code::ACCOUNT_DISABLED,
+            Error::MustBeAuthenticated => http::error::Code::MUST_BE_AUTHENTICATED,
+            Error::ForbiddenWhileImpersonating => http::error::Code::FORBIDDEN_WHILE_IMPERSONATING,
         };
 
-        let status_code = match err {
-            Error::UsernameTaken => http::StatusCode::CONFLICT,
+        let errors = match err {
+            Error::UsernameTaken => vec![http::error::FieldError {
+                field: String::from("username"),
+                message: err.to_string(),
+            }],
+            Error::PasswordBreached { field } => vec![http::error::FieldError {
+                field: String::from(field),
+                message: err.to_string(),
+            }],
+            Error::RegistrationDisabled
+            | Error::UserNotFound
+            | Error::WrongPassword
+            | Error::AccountDisabled
+            | Error::MustBeAuthenticated
+            | Error::ForbiddenWhileImpersonating => Vec::new(),
         };
 
         let message = err.to_string();
 
         http::Error {
             error_code,
-            status_code,
             message,
+            errors,
         }
     }
 }
@@ -0,0 +1,197 @@
+use std::time::SystemTime;
+
+use axum::{
+    extract::State,
+    http::{header, HeaderName, Request},
+    middleware::{self, Next},
+    response::{self, IntoResponse},
+    Router,
+};
+use thiserror::Error;
+use time::{macros::datetime, OffsetDateTime};
+
+use crate::{
+    config::Config,
+    http::{self, admin, audit, auth, impersonation, users},
+};
+
+pub(in crate::http) const DEPRECATION_HEADER: HeaderName = HeaderName::from_static("deprecation");
+pub(in crate::http) const SUNSET_HEADER: HeaderName = HeaderName::from_static("sunset");
+
+/// When serving the API without a version prefix was deprecated in favour of
+/// `/v1`
+const UNVERSIONED_DEPRECATED_AT: OffsetDateTime = datetime!(2026-10-19 0:00 UTC);
+
+/// Which of the API's routes a listener serves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(in crate::http) enum Routes
+{
+    All,
+    /// Everything but the admin API
+    Public,
+    Admin,
+}
+
+/// A version of the API, served under a prefix of its own so a new one can
+/// make breaking changes without breaking clients of the old one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Version
+{
+    V1,
+}
+
+impl Version
+{
+    /// Oldest first
+    const ALL: [Version; 1] = [Version::V1];
+    /// What the unversioned paths are aliases of
+    const LATEST: Version = Version::V1;
+
+    fn prefix(self) -> &'static str
+    {
+        match self {
+            Version::V1 => "/v1",
+        }
+    }
+
+    fn router(self, config: &Config, routes: Routes) -> Router
+    {
+        let (public, admin) = match self {
+            Version::V1 => (
+                Router::new()
+                    .merge(auth::router(config.limits()))
+                    .merge(users::router(config.limits()))
+                    .merge(impersonation::router()),
+                Router::new()
+                    .merge(admin::router())
+                    .merge(impersonation::admin_router())
+                    .merge(audit::router()),
+            ),
+        };
+
+        match routes {
+            Routes::All => public.merge(admin),
+            Routes::Public => public,
+            Routes::Admin => admin,
+        }
+    }
+
+    /// `None` for as long as the version isn't deprecated
+    fn deprecation(self) -> Option<Deprecation>
+    {
+        match self {
+            Version::V1 => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Deprecation
+{
+    deprecated_at: OffsetDateTime,
+    /// Requests are refused from then on
+    sunset: Option<OffsetDateTime>,
+    /// The prefix of the version replacing the deprecated one
+    successor: &'static str,
+}
+
+/// `routes` of every version of the API under its prefix, along with the
+/// unversioned aliases of the latest one unless `api.unversioned_aliases` is
+/// off
+pub(in crate::http) fn router(config: &Config, routes: Routes) -> Router
+{
+    let mut router = Router::new();
+
+    for version in Version::ALL {
+        let versioned = match version.deprecation() {
+            Some(deprecation) => version
+                .router(config, routes)
+                .route_layer(middleware::from_fn_with_state(deprecation, deprecated)),
+            None => version.router(config, routes),
+        };
+
+        router = router.nest(version.prefix(), versioned);
+    }
+
+    if config.api_unversioned_aliases() {
+        let deprecation = Deprecation {
+            deprecated_at: UNVERSIONED_DEPRECATED_AT,
+            sunset: config.api_unversioned_sunset(),
+            successor: Version::LATEST.prefix(),
+        };
+
+        router = router.merge(
+            Version::LATEST
+                .router(config, routes)
+                .route_layer(middleware::from_fn_with_state(deprecation, deprecated)),
+        );
+    }
+
+    router
+}
+
+/// Announces the deprecation on every response as in RFC 9745 and RFC 8594,
+/// and refuses requests once past the sunset
+async fn deprecated<B>(
+    State(deprecation): State<Deprecation>,
+    req: Request<B>,
+    next: Next<B>,
+) -> response::Response
+{
+    if deprecation
+        .sunset
+        .is_some_and(|sunset| sunset <= OffsetDateTime::now_utc())
+    {
+        return http::Error::from(Error::Gone).into_response();
+    }
+
+    // Nesting strips the version's own prefix, so this is the path within
+    // the version either way
+    let successor = format!(
+        r#"<{}{}>; rel="successor-version""#,
+        deprecation.successor,
+        req.uri().path()
+    );
+
+    let mut res = next.run(req).await;
+    let headers = res.headers_mut();
+
+    let deprecated_at = format!("@{}", deprecation.deprecated_at.unix_timestamp());
+    // SAFETY: An `@` followed by digits only
+    let _prev_value = headers.insert(DEPRECATION_HEADER, deprecated_at.parse().unwrap());
+    if let Some(sunset) = deprecation.sunset {
+        let sunset = httpdate::fmt_http_date(SystemTime::from(sunset));
+        // SAFETY: HTTP dates are ASCII only
+        let _prev_value = headers.insert(SUNSET_HEADER, sunset.parse().unwrap());
+    }
+    // Paths are percent-encoded by then, so this is ASCII only too unless
+    // the client sent something invalid
+    if let Ok(successor) = http::HeaderValue::from_str(&successor) {
+        let _appended = headers.append(header::LINK, successor);
+    }
+
+    res
+}
+
+#[derive(Debug, Error)]
+enum Error
+{
+    #[error("this version of the API is no longer served")]
+    Gone,
+}
+
+impl From<Error> for http::Error
+{
+    fn from(err: Error) -> Self
+    {
+        let error_code = match err {
+            Error::Gone => http::error::Code::API_VERSION_GONE,
+        };
+
+        http::Error {
+            error_code,
+            message: err.to_string(),
+            errors: Vec::new(),
+        }
+    }
+}
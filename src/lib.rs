@@ -15,8 +15,14 @@
 )]
 #![allow(clippy::new_without_default)]
 
+pub mod cli;
 pub mod config;
+pub mod db;
 
 pub mod http;
 
-mod password;
+mod audit;
+mod metrics;
+pub mod password;
+
+pub mod telemetry;
@@ -0,0 +1,91 @@
+use once_cell::sync::Lazy;
+use prometheus::{
+    core::Collector, Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+
+static REGISTRY: Lazy<Registry> = Lazy::new(Registry::new);
+
+// Argon2 is deliberately slow, so the default buckets (which top out at 10s
+// but start at 5ms) would put nearly every observation in the same few
+const PASSWORD_HASH_BUCKETS: &[f64] = &[0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5];
+
+pub(crate) static HTTP_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(IntCounterVec::new(
+        Opts::new("http_requests_total", "HTTP requests handled"),
+        &["method", "route", "status"],
+    ))
+});
+
+pub(crate) static HTTP_REQUEST_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register(HistogramVec::new(
+        HistogramOpts::new(
+            "http_request_duration_seconds",
+            "Time taken to handle HTTP requests",
+        ),
+        &["method", "route"],
+    ))
+});
+
+pub(crate) static HTTP_ERRORS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(IntCounterVec::new(
+        Opts::new("http_errors_total", "Error responses by error code"),
+        &["code"],
+    ))
+});
+
+pub(crate) static PASSWORD_HASH_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register(HistogramVec::new(
+        HistogramOpts::new(
+            "password_hash_duration_seconds",
+            "Time taken to hash or verify a password with Argon2",
+        )
+        .buckets(PASSWORD_HASH_BUCKETS.to_vec()),
+        &["operation"],
+    ))
+});
+
+pub(crate) static SESSION_STORE_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register(HistogramVec::new(
+        HistogramOpts::new(
+            "session_store_duration_seconds",
+            "Time taken by session store operations against Redis",
+        ),
+        &["operation"],
+    ))
+});
+
+pub(crate) static PG_POOL_CONNECTIONS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register(IntGaugeVec::new(
+        Opts::new(
+            "pg_pool_connections",
+            "Postgres pool connections, sampled when metrics are scraped",
+        ),
+        &["pool", "state"],
+    ))
+});
+
+fn register<M>(metric: prometheus::Result<M>) -> M
+where
+    M: Collector + Clone + 'static,
+{
+    // SAFETY: Every metric above has a static, valid and unique name and
+    // label set, so neither creating nor registering it can fail
+    let metric = metric.unwrap();
+    REGISTRY.register(Box::new(metric.clone())).unwrap();
+
+    metric
+}
+
+/// Renders every registered metric in the Prometheus text exposition format
+pub(crate) fn render() -> String
+{
+    let mut buffer = Vec::new();
+    // SAFETY: The text encoder only fails on metric families without a
+    // metric type, which the registry never produces, and always writes UTF-8
+    TextEncoder::new()
+        .encode(&REGISTRY.gather(), &mut buffer)
+        .unwrap();
+
+    String::from_utf8(buffer).unwrap()
+}
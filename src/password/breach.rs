@@ -0,0 +1,512 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::UNIX_EPOCH,
+};
+
+use tokio::task;
+
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use thiserror::Error;
+
+// Lookups are bucketed by the first 5 hex characters of the SHA-1 hash, the
+// same prefix length used by the Have-I-Been-Pwned range API
+const PREFIX_BITS: u32 = 20;
+const BUCKET_COUNT: usize = 1 << PREFIX_BITS;
+
+const HASH_HEX_LEN: usize = 40;
+const PREFIX_HEX_LEN: usize = 5;
+
+const INDEX_MAGIC: &[u8; 8] = b"MTBIDX1\0";
+const INDEX_EXTENSION: &str = "idx";
+// magic + dataset length + dataset modification time
+const INDEX_HEADER_LEN: u64 = 8 + 8 + 8;
+
+/// What to do when a candidate password is found in the breach dataset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Policy
+{
+    Reject,
+    Warn,
+}
+
+impl FromStr for Policy
+{
+    type Err = ParsePolicyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        match s {
+            "reject" => Ok(Policy::Reject),
+            "warn" => Ok(Policy::Warn),
+            _ => Err(ParsePolicyError(String::from(s))),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("invalid breached password policy `{0}`, expected `reject` or `warn`")]
+pub struct ParsePolicyError(String);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Verdict
+{
+    NotBreached,
+    Breached
+    {
+        occurrences: u64,
+        policy: Policy,
+    },
+}
+
+/// Checks candidate passwords against a local copy of the Have-I-Been-Pwned
+/// SHA-1 dataset, ordered by hash with one `HASH:COUNT` entry per line.
+///
+/// A sidecar index holding the byte offset of every 5 hex character prefix is
+/// kept next to the dataset, its path with the extension replaced by `idx`
+/// (`pwned.txt` is indexed in `pwned.idx`), so a lookup only ever reads the
+/// few hundred lines sharing the candidate's prefix.
+#[derive(Debug, Clone)]
+pub struct Checker
+{
+    dataset: Option<Arc<Dataset>>,
+    policy: Policy,
+}
+
+#[derive(Debug)]
+struct Dataset
+{
+    path: PathBuf,
+    index_path: PathBuf,
+}
+
+impl Checker
+{
+    pub fn disabled() -> Self
+    {
+        Checker {
+            dataset: None,
+            policy: Policy::Reject,
+        }
+    }
+
+    /// Opens the dataset at `path`, (re)building its index first if it is
+    /// missing or was built from a different version of the dataset
+    pub async fn open(path: impl Into<PathBuf>, policy: Policy) -> Result<Self, self::Error>
+    {
+        let path = path.into();
+        let index_path = path.with_extension(INDEX_EXTENSION);
+
+        let dataset = task::spawn_blocking(move || {
+            let fingerprint = Fingerprint::of(&path)?;
+
+            if Fingerprint::of_index(&index_path)? != Some(fingerprint) {
+                build_index(&path, &index_path, fingerprint)?;
+            }
+
+            Ok::<_, self::Error>(Dataset { path, index_path })
+        })
+        .await??;
+
+        Ok(Checker {
+            dataset: Some(Arc::new(dataset)),
+            policy,
+        })
+    }
+
+    pub(crate) async fn check(&self, password: &str) -> Result<Verdict, self::Error>
+    {
+        let dataset = match &self.dataset {
+            Some(dataset) => dataset.clone(),
+            None => return Ok(Verdict::NotBreached),
+        };
+
+        let hash = format!("{:X}", Sha1::digest(password.as_bytes()));
+        let policy = self.policy;
+
+        task::spawn_blocking(move || match dataset.occurrences(&hash)? {
+            Some(occurrences) => Ok(Verdict::Breached {
+                occurrences,
+                policy,
+            }),
+            None => Ok(Verdict::NotBreached),
+        })
+        .await?
+    }
+}
+
+impl Dataset
+{
+    fn occurrences(&self, hash: &str) -> Result<Option<u64>, self::Error>
+    {
+        // SAFETY: `hash` is always a hex-encoded SHA-1 digest
+        let bucket = usize::from_str_radix(&hash[..PREFIX_HEX_LEN], 16).unwrap();
+
+        let mut index = File::open(&self.index_path)?;
+        let _position = index.seek(SeekFrom::Start(INDEX_HEADER_LEN + 8 * bucket as u64))?;
+        let mut offsets = [0; 16];
+        index.read_exact(&mut offsets)?;
+        // SAFETY: Both halves are exactly 8 bytes long
+        let start = u64::from_le_bytes(offsets[..8].try_into().unwrap());
+        let end = u64::from_le_bytes(offsets[8..].try_into().unwrap());
+
+        let mut dataset = File::open(&self.path)?;
+        let _position = dataset.seek(SeekFrom::Start(start))?;
+        let mut lines = BufReader::new(dataset.take(end - start)).lines();
+
+        lines
+            .find_map(|line| {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(err) => return Some(Err(self::Error::from(err))),
+                };
+
+                match parse_line(&line) {
+                    Some((line_hash, count)) if line_hash.eq_ignore_ascii_case(hash) => {
+                        Some(Ok(count))
+                    }
+                    Some(_) => None,
+                    None => Some(Err(self::Error::MalformedDataset)),
+                }
+            })
+            .transpose()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Fingerprint
+{
+    len: u64,
+    modified: u64,
+}
+
+impl Fingerprint
+{
+    fn of(path: &Path) -> Result<Self, self::Error>
+    {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|modified| modified.as_secs())
+            .unwrap_or_default();
+
+        Ok(Fingerprint {
+            len: metadata.len(),
+            modified,
+        })
+    }
+
+    fn of_index(index_path: &Path) -> Result<Option<Self>, self::Error>
+    {
+        let mut index = match File::open(index_path) {
+            Ok(index) => index,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => Err(err)?,
+        };
+
+        let mut header = [0; INDEX_HEADER_LEN as usize];
+        match index.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => Err(err)?,
+        }
+
+        if &header[..8] != INDEX_MAGIC {
+            return Ok(None);
+        }
+
+        // SAFETY: Both fields are exactly 8 bytes long
+        Ok(Some(Fingerprint {
+            len: u64::from_le_bytes(header[8..16].try_into().unwrap()),
+            modified: u64::from_le_bytes(header[16..24].try_into().unwrap()),
+        }))
+    }
+}
+
+fn build_index(
+    path: &Path,
+    index_path: &Path,
+    fingerprint: Fingerprint,
+) -> Result<(), self::Error>
+{
+    let mut offsets = vec![0u64; BUCKET_COUNT + 1];
+    let mut next_bucket = 0;
+    let mut offset = 0;
+
+    let mut dataset = BufReader::new(File::open(path)?);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        let read = dataset.read_line(&mut line)?;
+        if read == 0 {
+            break;
+        }
+
+        let (hash, _count) = parse_line(line.trim_end()).ok_or(self::Error::MalformedDataset)?;
+        // SAFETY: `parse_line` only returns hashes made of hex digits
+        let bucket = usize::from_str_radix(&hash[..PREFIX_HEX_LEN], 16).unwrap();
+
+        if bucket + 1 < next_bucket {
+            Err(self::Error::UnsortedDataset)?;
+        }
+        while next_bucket <= bucket {
+            offsets[next_bucket] = offset;
+            next_bucket += 1;
+        }
+
+        offset += read as u64;
+    }
+    for remaining in &mut offsets[next_bucket..] {
+        *remaining = offset;
+    }
+
+    // Write to a temporary file first so a crash midway never leaves a
+    // truncated index that would be mistaken for a valid one
+    let partial_path = index_path.with_extension("idx.partial");
+    let mut index = BufWriter::new(File::create(&partial_path)?);
+    index.write_all(INDEX_MAGIC)?;
+    index.write_all(&fingerprint.len.to_le_bytes())?;
+    index.write_all(&fingerprint.modified.to_le_bytes())?;
+    for offset in offsets {
+        index.write_all(&offset.to_le_bytes())?;
+    }
+    index
+        .into_inner()
+        .map_err(io::IntoInnerError::into_error)?
+        .sync_all()?;
+    fs::rename(partial_path, index_path)?;
+
+    Ok(())
+}
+
+fn parse_line(line: &str) -> Option<(&str, u64)>
+{
+    let (hash, count) = line.split_once(':')?;
+
+    if hash.len() != HASH_HEX_LEN || !hash.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    Some((hash, count.trim().parse().ok()?))
+}
+
+#[derive(Debug, Error)]
+pub enum Error
+{
+    #[error("{inner}")]
+    Io
+    {
+        #[from]
+        inner: io::Error,
+    },
+    #[error("{inner}")]
+    TaskJoin
+    {
+        #[from]
+        inner: task::JoinError,
+    },
+    #[error("breached password dataset contains a malformed line")]
+    MalformedDataset,
+    #[error("breached password dataset is not ordered by hash")]
+    UnsortedDataset,
+}
+
+#[cfg(test)]
+mod tests
+{
+    use std::env;
+
+    use super::*;
+
+    const FIRST: &str = "00000A94A8FE5CCB19BA61C4C0873D391E987982";
+    const MIDDLE_1: &str = "7C4A8D09CA3762AF61E59520943DC26494F8941B";
+    const MIDDLE_2: &str = "7C4A8FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF";
+    const LAST: &str = "FFFFF00000000000000000000000000000000000";
+
+    /// A dataset made of `lines` in a directory of its own, removed on drop
+    struct TestDataset
+    {
+        dir: PathBuf,
+        path: PathBuf,
+        index_path: PathBuf,
+    }
+
+    impl TestDataset
+    {
+        fn write(lines: &[&str]) -> Self
+        {
+            let dir =
+                env::temp_dir().join(format!("mindtrails_breach_{:016x}", rand::random::<u64>()));
+            fs::create_dir_all(&dir).unwrap();
+            let path = dir.join("pwned.txt");
+            fs::write(&path, lines.join("\r\n")).unwrap();
+
+            TestDataset {
+                index_path: path.with_extension(INDEX_EXTENSION),
+                path,
+                dir,
+            }
+        }
+
+        fn build_index(&self) -> Result<Fingerprint, self::Error>
+        {
+            let fingerprint = Fingerprint::of(&self.path)?;
+            build_index(&self.path, &self.index_path, fingerprint)?;
+            Ok(fingerprint)
+        }
+
+        fn offsets(&self) -> Vec<u64>
+        {
+            fs::read(&self.index_path).unwrap()[INDEX_HEADER_LEN as usize..]
+                .chunks_exact(8)
+                .map(|offset| u64::from_le_bytes(offset.try_into().unwrap()))
+                .collect()
+        }
+
+        fn dataset(&self) -> Dataset
+        {
+            Dataset {
+                path: self.path.clone(),
+                index_path: self.index_path.clone(),
+            }
+        }
+    }
+
+    impl Drop for TestDataset
+    {
+        fn drop(&mut self)
+        {
+            let _removed = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn entry(hash: &str, count: u64) -> String
+    {
+        format!("{hash}:{count}")
+    }
+
+    #[test]
+    fn parse_line_reads_hash_and_count()
+    {
+        assert_eq!(parse_line(&entry(MIDDLE_1, 42)), Some((MIDDLE_1, 42)));
+        assert_eq!(
+            parse_line(&format!("{}:7 ", MIDDLE_1.to_lowercase())),
+            Some((MIDDLE_1.to_lowercase().as_str(), 7))
+        );
+    }
+
+    #[test]
+    fn parse_line_rejects_malformed_lines()
+    {
+        assert_eq!(parse_line(MIDDLE_1), None);
+        assert_eq!(parse_line(&entry(&MIDDLE_1[1..], 1)), None);
+        assert_eq!(parse_line(&format!("{MIDDLE_1}0:1")), None);
+        assert_eq!(parse_line(&entry(&MIDDLE_1.replace('A', "G"), 1)), None);
+        assert_eq!(parse_line(&format!("{MIDDLE_1}:")), None);
+        assert_eq!(parse_line(&format!("{MIDDLE_1}:-1")), None);
+    }
+
+    #[test]
+    fn the_index_has_an_offset_for_every_bucket_and_the_end()
+    {
+        let lines = [
+            entry(FIRST, 1),
+            entry(MIDDLE_1, 2),
+            entry(MIDDLE_2, 3),
+            entry(LAST, 4),
+        ];
+        let dataset = TestDataset::write(&lines.iter().map(String::as_str).collect::<Vec<_>>());
+        let fingerprint = dataset.build_index().unwrap();
+
+        let offsets = dataset.offsets();
+        assert_eq!(offsets.len(), BUCKET_COUNT + 1);
+
+        // Every line but the last one ends with `\r\n`
+        let line_len = HASH_HEX_LEN as u64 + 2 + 2;
+        let middle = 0x7C4A8;
+        assert_eq!(offsets[0], 0);
+        assert_eq!(offsets[1], line_len);
+        assert_eq!(offsets[middle], line_len);
+        assert_eq!(offsets[middle + 1], 3 * line_len);
+        assert_eq!(offsets[BUCKET_COUNT - 1], 3 * line_len);
+        assert_eq!(offsets[BUCKET_COUNT], fingerprint.len);
+        assert!(offsets.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        assert_eq!(
+            Fingerprint::of_index(&dataset.index_path).unwrap(),
+            Some(fingerprint)
+        );
+    }
+
+    #[test]
+    fn lookups_find_only_the_hashes_in_the_dataset()
+    {
+        let lines = [
+            entry(FIRST, 1),
+            entry(MIDDLE_1, 2),
+            entry(MIDDLE_2, 3),
+            entry(LAST, 4),
+        ];
+        let dataset = TestDataset::write(&lines.iter().map(String::as_str).collect::<Vec<_>>());
+        let _fingerprint = dataset.build_index().unwrap();
+        let dataset = dataset.dataset();
+
+        assert_eq!(dataset.occurrences(FIRST).unwrap(), Some(1));
+        assert_eq!(dataset.occurrences(MIDDLE_1).unwrap(), Some(2));
+        assert_eq!(dataset.occurrences(MIDDLE_2).unwrap(), Some(3));
+        assert_eq!(dataset.occurrences(LAST).unwrap(), Some(4));
+        assert_eq!(
+            dataset
+                .occurrences("7C4A800000000000000000000000000000000000")
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            dataset
+                .occurrences("1234500000000000000000000000000000000000")
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn an_unsorted_dataset_is_refused()
+    {
+        let lines = [entry(MIDDLE_1, 1), entry(FIRST, 2)];
+        let dataset = TestDataset::write(&lines.iter().map(String::as_str).collect::<Vec<_>>());
+
+        assert!(matches!(
+            dataset.build_index(),
+            Err(self::Error::UnsortedDataset)
+        ));
+        assert!(!dataset.index_path.exists());
+    }
+
+    #[test]
+    fn an_index_from_another_version_of_the_dataset_is_told_apart()
+    {
+        let dataset = TestDataset::write(&[&entry(MIDDLE_1, 1)]);
+        let fingerprint = dataset.build_index().unwrap();
+
+        fs::write(&dataset.path, entry(MIDDLE_2, 1) + "\n" + &entry(LAST, 1)).unwrap();
+
+        assert_ne!(Fingerprint::of(&dataset.path).unwrap(), fingerprint);
+        assert_eq!(
+            Fingerprint::of_index(&dataset.index_path).unwrap(),
+            Some(fingerprint)
+        );
+        assert_eq!(
+            Fingerprint::of_index(&dataset.dir.join("missing.idx")).unwrap(),
+            None
+        );
+
+        fs::write(&dataset.index_path, b"garbage").unwrap();
+        assert_eq!(Fingerprint::of_index(&dataset.index_path).unwrap(), None);
+    }
+}
@@ -0,0 +1,91 @@
+use tokio::task;
+
+use argon2::{
+    password_hash::{self, SaltString},
+    Algorithm, Argon2, Params, PasswordHash, PasswordHasher, PasswordVerifier, Version,
+};
+use thiserror::Error;
+
+use crate::metrics;
+
+pub mod breach;
+
+/// Hashes new passwords with the configured Argon2 cost parameters
+#[derive(Debug, Clone)]
+pub(crate) struct Hasher
+{
+    params: Params,
+}
+
+impl Hasher
+{
+    pub(crate) fn new(params: Params) -> Self
+    {
+        Hasher { params }
+    }
+
+    pub(crate) async fn hash(&self, password: String) -> Result<String, self::Error>
+    {
+        let params = self.params.clone();
+
+        let password = task::spawn_blocking(move || {
+            let _timer = metrics::PASSWORD_HASH_DURATION
+                .with_label_values(&["hash"])
+                .start_timer();
+
+            let salt = SaltString::generate(rand::thread_rng());
+
+            let hashed_password = Argon2::new(Algorithm::default(), Version::default(), params)
+                .hash_password(password.as_bytes(), &salt)?;
+
+            Ok(hashed_password.to_string())
+        })
+        .await?;
+
+        password
+    }
+}
+
+/// Verifies `password` against `hash`, using whatever parameters the hash was
+/// made with so changing them doesn't lock anyone out
+pub(crate) async fn verify(password: String, hash: String) -> Result<bool, self::Error>
+{
+    task::spawn_blocking(move || {
+        let _timer = metrics::PASSWORD_HASH_DURATION
+            .with_label_values(&["verify"])
+            .start_timer();
+
+        let hash = PasswordHash::new(&hash).map_err(Error::from)?;
+
+        match Argon2::default().verify_password(password.as_bytes(), &hash) {
+            Ok(()) => Ok(true),
+            Err(password_hash::Error::Password) => Ok(false),
+            Err(err) => Err(err)?,
+        }
+    })
+    .await?
+}
+
+#[allow(variant_size_differences)]
+#[derive(Debug, Error)]
+pub enum Error
+{
+    #[error("{inner}")]
+    PasswordHash
+    {
+        #[from]
+        inner: password_hash::Error,
+    },
+    #[error("{inner}")]
+    Breach
+    {
+        #[from]
+        inner: breach::Error,
+    },
+    #[error("{inner}")]
+    TaskJoin
+    {
+        #[from]
+        inner: task::JoinError,
+    },
+}
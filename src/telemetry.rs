@@ -0,0 +1,106 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Serialize, Serializer};
+use thiserror::Error;
+use tracing_subscriber::{filter::ParseError, prelude::*, reload, EnvFilter, Registry};
+
+/// How log lines are written to stdout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat
+{
+    /// Human readable, for local development
+    Pretty,
+    /// One JSON object per line, for log collectors in production
+    Json,
+}
+
+impl FromStr for LogFormat
+{
+    type Err = ParseLogFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        match s {
+            "pretty" => Ok(LogFormat::Pretty),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(ParseLogFormatError(String::from(s))),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+#[error("invalid log format `{0}`, expected `pretty` or `json`")]
+pub struct ParseLogFormatError(String);
+
+/// Which log lines are kept, in the same syntax as `RUST_LOG`
+/// (e.g. `info,sqlx::query=warn`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFilter(String);
+
+impl FromStr for LogFilter
+{
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err>
+    {
+        let _filter = EnvFilter::try_new(s)?;
+
+        Ok(LogFilter(String::from(s)))
+    }
+}
+
+impl fmt::Display for LogFilter
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for LogFilter
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+impl LogFilter
+{
+    fn to_env_filter(&self) -> EnvFilter
+    {
+        // SAFETY: The filter was already parsed successfully when created
+        EnvFilter::try_new(&self.0).unwrap()
+    }
+}
+
+/// Swaps the filter of the subscriber installed by `init` while running
+#[derive(Debug, Clone)]
+pub struct LogFilterHandle(reload::Handle<EnvFilter, Registry>);
+
+impl LogFilterHandle
+{
+    pub fn set(&self, filter: &LogFilter) -> Result<(), reload::Error>
+    {
+        self.0.reload(filter.to_env_filter())
+    }
+}
+
+/// Installs the global `tracing` subscriber
+pub fn init(format: LogFormat, filter: &LogFilter) -> LogFilterHandle
+{
+    let (filter, handle) = reload::Layer::new(filter.to_env_filter());
+    let subscriber = tracing_subscriber::registry().with(filter);
+
+    match format {
+        LogFormat::Pretty => subscriber.with(tracing_subscriber::fmt::layer()).init(),
+        LogFormat::Json => subscriber
+            .with(tracing_subscriber::fmt::layer().json().flatten_event(true))
+            .init(),
+    }
+
+    LogFilterHandle(handle)
+}
@@ -0,0 +1,121 @@
+use axum::http::StatusCode;
+use serde_json::json;
+use sqlx::Executor;
+
+mod common;
+use common::{TestApp, PASSWORD};
+
+async fn logged_in_admin() -> TestApp
+{
+    let mut app = TestApp::spawn().await;
+    let admin_id = app.register("admin", PASSWORD).await;
+    app.grant_role(admin_id, "admin").await;
+    app.login("admin", PASSWORD).await;
+
+    app
+}
+
+#[tokio::test]
+async fn list_users_pages_through_users()
+{
+    let mut app = logged_in_admin().await;
+    app.register("alice", PASSWORD).await;
+    app.register("bob", PASSWORD).await;
+
+    let res = app.get("/v1/admin/users?page=2&per_page=2").await;
+
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(res.json()["total"], 3);
+    assert_eq!(res.json()["users"][0]["username"], "bob");
+}
+
+#[tokio::test]
+async fn list_users_rejects_a_page_too_large_to_reach()
+{
+    let mut app = logged_in_admin().await;
+
+    let res = app.get(&format!("/v1/admin/users?page={}", i64::MAX)).await;
+
+    res.assert_error(StatusCode::BAD_REQUEST, 302);
+}
+
+#[tokio::test]
+async fn list_audit_entries_rejects_a_page_too_large_to_reach()
+{
+    let mut app = logged_in_admin().await;
+
+    let res = app.get(&format!("/v1/admin/audit?page={}", i64::MAX)).await;
+
+    res.assert_error(StatusCode::BAD_REQUEST, 302);
+}
+
+#[tokio::test]
+async fn revoking_user_sessions_logs_the_user_out_everywhere()
+{
+    let mut app = logged_in_admin().await;
+    let admin_cookies = app.take_cookies();
+    let user_id = app.register("alice", PASSWORD).await;
+    app.login("alice", PASSWORD).await;
+    let first_session = app.take_cookies();
+    app.login("alice", PASSWORD).await;
+    let second_session = app.take_cookies();
+
+    app.set_cookies(admin_cookies);
+    let res = app
+        .post_json(
+            &format!("/v1/admin/users/{user_id}/sessions/revoke"),
+            json!({}),
+        )
+        .await;
+    assert!(res.status.is_success(), "{}", res.text());
+    assert_eq!(res.json()["revoked"], 2, "{}", res.text());
+
+    for cookies in [first_session, second_session] {
+        app.set_cookies(cookies);
+        app.get("/v1/auth")
+            .await
+            .assert_error(StatusCode::BAD_REQUEST, 201);
+    }
+}
+
+#[tokio::test]
+async fn the_audit_log_verifies_while_untouched()
+{
+    let mut app = logged_in_admin().await;
+    app.register("alice", PASSWORD).await;
+
+    let res = app.get("/v1/admin/audit/verify").await;
+
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(res.json()["intact"], true);
+    // Both users created and the admin's login
+    assert_eq!(res.json()["entries"], 3);
+}
+
+#[tokio::test]
+async fn rewriting_the_audit_log_without_the_key_breaks_the_chain()
+{
+    let mut app = logged_in_admin().await;
+    app.register("alice", PASSWORD).await;
+
+    // Everything someone with write access to the database could do, short of
+    // knowing the key
+    app.pg_pool()
+        .execute(
+            r#"
+                ALTER TABLE audit_log DISABLE TRIGGER audit_log_append_only;
+                UPDATE audit_log
+                SET details = '{"via": "cli"}', hash = sha256(hash)
+                WHERE audit_id = 2;
+                ALTER TABLE audit_log ENABLE TRIGGER audit_log_append_only;
+            "#,
+        )
+        .await
+        .unwrap();
+
+    let res = app.get("/v1/admin/audit/verify").await;
+
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(res.json()["intact"], false);
+    assert_eq!(res.json()["broken_at"], 2);
+}
@@ -0,0 +1,446 @@
+use std::{net::SocketAddr, time::Duration};
+
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{header, Request, StatusCode},
+};
+use serde_json::json;
+
+mod common;
+use common::{TestApp, TestResponse, PASSWORD};
+
+#[tokio::test]
+async fn fetch_auth_session_returns_the_logged_in_user()
+{
+    let mut app = TestApp::spawn().await;
+    let user_id = app.register("alice", PASSWORD).await;
+    app.login("alice", PASSWORD).await;
+
+    let res = app.get("/v1/auth").await;
+
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(res.text(), user_id.to_string());
+    assert!(res.headers.get("x-impersonator-id").is_none());
+}
+
+#[tokio::test]
+async fn fetch_auth_session_requires_a_session()
+{
+    let mut app = TestApp::spawn().await;
+
+    let res = app.get("/v1/auth").await;
+
+    res.assert_error(StatusCode::UNAUTHORIZED, 403);
+}
+
+#[tokio::test]
+async fn fetch_auth_session_rejects_an_unknown_session()
+{
+    let mut app = TestApp::spawn().await;
+    app.register("alice", PASSWORD).await;
+    app.login("alice", PASSWORD).await;
+    let cookies = app.take_cookies();
+
+    // Logging out destroys the session, the cookie is then sent again anyway
+    app.set_cookies(cookies.clone());
+    let res = app.delete("/v1/auth").await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+    app.set_cookies(cookies);
+
+    let res = app.get("/v1/auth").await;
+
+    res.assert_error(StatusCode::BAD_REQUEST, 201);
+}
+
+#[tokio::test]
+async fn fetch_auth_session_reports_the_impersonator()
+{
+    let mut app = TestApp::spawn().await;
+    let user_id = app.register("alice", PASSWORD).await;
+    let admin_id = app.register("admin", PASSWORD).await;
+    app.grant_role(admin_id, "admin").await;
+    app.login("admin", PASSWORD).await;
+
+    let res = app
+        .post_json(&format!("/v1/admin/impersonate/{user_id}"), json!({}))
+        .await;
+    assert!(res.status.is_success(), "{}", res.text());
+
+    let res = app.get("/v1/auth").await;
+
+    assert_eq!(res.status, StatusCode::OK);
+    assert_eq!(res.text(), user_id.to_string());
+    assert_eq!(res.headers["x-impersonator-id"], admin_id.to_string());
+}
+
+#[tokio::test]
+async fn create_auth_session_sets_the_session_cookie()
+{
+    let mut app = TestApp::spawn().await;
+    app.register("alice", PASSWORD).await;
+
+    let res = app
+        .post_json(
+            "/v1/auth",
+            json!({ "username": "alice", "password": PASSWORD }),
+        )
+        .await;
+
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+    let set_cookie = res.headers[header::SET_COOKIE].to_str().unwrap();
+    assert!(set_cookie.starts_with("mindtrails_session="));
+    assert!(set_cookie.contains("; HttpOnly"));
+    // The test client keeps cookies whatever their path, unlike browsers
+    assert!(set_cookie.contains("; Path=/;"));
+}
+
+#[tokio::test]
+async fn create_auth_session_rejects_an_unknown_user()
+{
+    let mut app = TestApp::spawn().await;
+
+    let res = app
+        .post_json(
+            "/v1/auth",
+            json!({ "username": "nobody", "password": PASSWORD }),
+        )
+        .await;
+
+    res.assert_error(StatusCode::NOT_FOUND, 401);
+}
+
+#[tokio::test]
+async fn create_auth_session_rejects_a_wrong_password()
+{
+    let mut app = TestApp::spawn().await;
+    app.register("alice", PASSWORD).await;
+
+    let res = app
+        .post_json(
+            "/v1/auth",
+            json!({ "username": "alice", "password": "wrong" }),
+        )
+        .await;
+
+    res.assert_error(StatusCode::UNPROCESSABLE_ENTITY, 402);
+}
+
+#[tokio::test]
+async fn create_auth_session_rejects_a_disabled_account()
+{
+    let mut app = TestApp::spawn().await;
+    let user_id = app.register("alice", PASSWORD).await;
+    let _pg_query_res = sqlx::query("UPDATE users SET disabled = true WHERE user_id = $1")
+        .bind(user_id)
+        .execute(app.pg_pool())
+        .await
+        .unwrap();
+
+    let res = app
+        .post_json(
+            "/v1/auth",
+            json!({ "username": "alice", "password": PASSWORD }),
+        )
+        .await;
+
+    res.assert_error(StatusCode::FORBIDDEN, 405);
+}
+
+#[tokio::test]
+async fn create_auth_session_is_refused_until_the_password_is_reset()
+{
+    let mut app = TestApp::spawn().await;
+    let user_id = app.register("alice", PASSWORD).await;
+    let _pg_query_res =
+        sqlx::query("UPDATE users SET password_reset_required = true WHERE user_id = $1")
+            .bind(user_id)
+            .execute(app.pg_pool())
+            .await
+            .unwrap();
+
+    let res = app
+        .post_json(
+            "/v1/auth",
+            json!({ "username": "alice", "password": PASSWORD }),
+        )
+        .await;
+
+    res.assert_error(StatusCode::FORBIDDEN, 406);
+}
+
+/// Tries logging in as alice from `peer`
+async fn login_from(app: &mut TestApp, peer: &str, password: &str) -> TestResponse
+{
+    let mut req = Request::post("/v1/auth")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            json!({ "username": "alice", "password": password }).to_string(),
+        ))
+        .unwrap();
+    let _prev_value = req
+        .extensions_mut()
+        .insert(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+
+    app.request(req).await
+}
+
+#[tokio::test]
+async fn create_auth_session_limits_failed_attempts_per_client()
+{
+    // The harness allows 3 attempts
+    let mut app = TestApp::spawn().await;
+    app.register("alice", PASSWORD).await;
+
+    for _ in 0..3 {
+        login_from(&mut app, "203.0.113.1:1234", "wrong")
+            .await
+            .assert_error(StatusCode::UNPROCESSABLE_ENTITY, 402);
+    }
+
+    // Even the right password is refused from there until the window is over
+    login_from(&mut app, "203.0.113.1:1234", PASSWORD)
+        .await
+        .assert_error(StatusCode::TOO_MANY_REQUESTS, 410);
+
+    // But alice isn't locked out anywhere else
+    let res = login_from(&mut app, "198.51.100.1:1234", PASSWORD).await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT, "{}", res.text());
+}
+
+#[tokio::test]
+async fn create_auth_session_limits_attempts_in_a_fixed_window()
+{
+    let mut app = TestApp::builder()
+        .config("rate_limit.login_window_secs", 1)
+        .spawn()
+        .await;
+    app.register("alice", PASSWORD).await;
+
+    login_from(&mut app, "203.0.113.1:1234", "wrong")
+        .await
+        .assert_error(StatusCode::UNPROCESSABLE_ENTITY, 402);
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    for _ in 0..2 {
+        login_from(&mut app, "203.0.113.1:1234", "wrong")
+            .await
+            .assert_error(StatusCode::UNPROCESSABLE_ENTITY, 402);
+    }
+
+    // Failing again didn't push the end of the window back
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    let res = login_from(&mut app, "203.0.113.1:1234", PASSWORD).await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT, "{}", res.text());
+}
+
+#[tokio::test]
+async fn create_auth_session_resets_the_limit_on_success()
+{
+    let mut app = TestApp::spawn().await;
+    app.register("alice", PASSWORD).await;
+
+    for _ in 0..2 {
+        let res = app
+            .post_json(
+                "/v1/auth",
+                json!({ "username": "alice", "password": "wrong" }),
+            )
+            .await;
+        res.assert_error(StatusCode::UNPROCESSABLE_ENTITY, 402);
+    }
+    app.login("alice", PASSWORD).await;
+
+    let res = app
+        .post_json(
+            "/v1/auth",
+            json!({ "username": "alice", "password": "wrong" }),
+        )
+        .await;
+
+    res.assert_error(StatusCode::UNPROCESSABLE_ENTITY, 402);
+}
+
+#[tokio::test]
+async fn create_auth_session_rejects_malformed_json()
+{
+    let mut app = TestApp::spawn().await;
+
+    let res = app
+        .post_raw("/v1/auth", Some("application/json"), r#"{"username":"#)
+        .await;
+
+    res.assert_error(StatusCode::BAD_REQUEST, 100);
+}
+
+#[tokio::test]
+async fn create_auth_session_rejects_missing_fields()
+{
+    let mut app = TestApp::spawn().await;
+
+    let res = app
+        .post_json("/v1/auth", json!({ "username": "alice" }))
+        .await;
+
+    res.assert_error(StatusCode::UNPROCESSABLE_ENTITY, 110);
+}
+
+#[tokio::test]
+async fn create_auth_session_requires_a_json_content_type()
+{
+    let mut app = TestApp::spawn().await;
+
+    let res = app
+        .post_raw("/v1/auth", None, r#"{"username":"alice","password":"x"}"#)
+        .await;
+
+    res.assert_error(StatusCode::UNSUPPORTED_MEDIA_TYPE, 120);
+}
+
+#[tokio::test]
+async fn delete_auth_session_logs_out()
+{
+    let mut app = TestApp::spawn().await;
+    app.register("alice", PASSWORD).await;
+    app.login("alice", PASSWORD).await;
+
+    let res = app.delete("/v1/auth").await;
+
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+    let set_cookie = res.headers[header::SET_COOKIE].to_str().unwrap();
+    assert!(set_cookie.contains("Max-Age=0"));
+    assert!(set_cookie.contains("; Path=/;"));
+    app.get("/v1/auth")
+        .await
+        .assert_error(StatusCode::UNAUTHORIZED, 403);
+}
+
+#[tokio::test]
+async fn delete_auth_session_requires_a_session()
+{
+    let mut app = TestApp::spawn().await;
+
+    let res = app.delete("/v1/auth").await;
+
+    res.assert_error(StatusCode::UNAUTHORIZED, 403);
+}
+
+#[tokio::test]
+async fn delete_auth_session_is_refused_while_impersonating()
+{
+    let mut app = TestApp::spawn().await;
+    let user_id = app.register("alice", PASSWORD).await;
+    let admin_id = app.register("admin", PASSWORD).await;
+    app.grant_role(admin_id, "admin").await;
+    app.login("admin", PASSWORD).await;
+    let res = app
+        .post_json(&format!("/v1/admin/impersonate/{user_id}"), json!({}))
+        .await;
+    assert!(res.status.is_success(), "{}", res.text());
+
+    let res = app.delete("/v1/auth").await;
+
+    res.assert_error(StatusCode::FORBIDDEN, 407);
+}
+
+#[tokio::test]
+async fn impersonating_another_admin_is_refused()
+{
+    let mut app = TestApp::spawn().await;
+    let admin_id = app.register("admin", PASSWORD).await;
+    app.grant_role(admin_id, "admin").await;
+    let other_admin_id = app.register("other_admin", PASSWORD).await;
+    app.grant_role(other_admin_id, "admin").await;
+    app.login("admin", PASSWORD).await;
+
+    let res = app
+        .post_json(
+            &format!("/v1/admin/impersonate/{other_admin_id}"),
+            json!({}),
+        )
+        .await;
+
+    res.assert_error(StatusCode::FORBIDDEN, 413);
+    // Still logged in as themselves
+    let res = app.get("/v1/auth").await;
+    assert_eq!(res.text(), admin_id.to_string());
+}
+
+#[tokio::test]
+async fn impersonating_a_disabled_account_is_refused()
+{
+    let mut app = TestApp::spawn().await;
+    let user_id = app.register("alice", PASSWORD).await;
+    let admin_id = app.register("admin", PASSWORD).await;
+    app.grant_role(admin_id, "admin").await;
+    app.login("admin", PASSWORD).await;
+    let res = app
+        .post_json(&format!("/v1/admin/users/{user_id}/disable"), json!({}))
+        .await;
+    assert!(res.status.is_success(), "{}", res.text());
+
+    let res = app
+        .post_json(&format!("/v1/admin/impersonate/{user_id}"), json!({}))
+        .await;
+
+    res.assert_error(StatusCode::UNPROCESSABLE_ENTITY, 412);
+}
+
+#[tokio::test]
+async fn disabling_an_admin_ends_their_impersonations()
+{
+    let mut app = TestApp::spawn().await;
+    let user_id = app.register("alice", PASSWORD).await;
+    let admin_id = app.register("admin", PASSWORD).await;
+    app.grant_role(admin_id, "admin").await;
+    let other_admin_id = app.register("other_admin", PASSWORD).await;
+    app.grant_role(other_admin_id, "admin").await;
+
+    app.login("other_admin", PASSWORD).await;
+    let other_admin_cookies = app.take_cookies();
+    app.login("admin", PASSWORD).await;
+    let res = app
+        .post_json(&format!("/v1/admin/impersonate/{user_id}"), json!({}))
+        .await;
+    assert!(res.status.is_success(), "{}", res.text());
+    let impersonation_cookies = app.take_cookies();
+
+    app.set_cookies(other_admin_cookies);
+    let res = app
+        .post_json(&format!("/v1/admin/users/{admin_id}/disable"), json!({}))
+        .await;
+    assert!(res.status.is_success(), "{}", res.text());
+
+    app.set_cookies(impersonation_cookies);
+    app.get("/v1/auth")
+        .await
+        .assert_error(StatusCode::BAD_REQUEST, 201);
+}
+
+#[tokio::test]
+async fn ending_an_impersonation_is_refused_to_a_disabled_admin()
+{
+    let mut app = TestApp::spawn().await;
+    let user_id = app.register("alice", PASSWORD).await;
+    let admin_id = app.register("admin", PASSWORD).await;
+    app.grant_role(admin_id, "admin").await;
+    app.login("admin", PASSWORD).await;
+    let res = app
+        .post_json(&format!("/v1/admin/impersonate/{user_id}"), json!({}))
+        .await;
+    assert!(res.status.is_success(), "{}", res.text());
+
+    // Behind the app's back, so the impersonation session is still around
+    let _pg_query_res = sqlx::query("UPDATE users SET disabled = true WHERE user_id = $1")
+        .bind(admin_id)
+        .execute(app.pg_pool())
+        .await
+        .unwrap();
+
+    app.delete("/v1/auth/impersonation")
+        .await
+        .assert_error(StatusCode::FORBIDDEN, 405);
+    // Nor is the impersonation left going
+    app.get("/v1/auth")
+        .await
+        .assert_error(StatusCode::BAD_REQUEST, 201);
+}
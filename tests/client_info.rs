@@ -0,0 +1,160 @@
+use std::net::SocketAddr;
+
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{header, Request, StatusCode},
+};
+use serde_json::{json, Value};
+
+mod common;
+use common::{TestApp, PASSWORD};
+
+async fn spawn() -> TestApp
+{
+    let mut app = TestApp::builder()
+        .config("proxies.trusted", vec!["10.0.0.0/8", "fd00::/8"])
+        .spawn()
+        .await;
+    app.register("alice", PASSWORD).await;
+
+    app
+}
+
+/// Logs in from `peer` with the given headers, and returns the details the
+/// login was audited with
+async fn login_from(app: &mut TestApp, peer: &str, headers: &[(&str, &str)]) -> Value
+{
+    let mut req = Request::post("/v1/auth").header(header::CONTENT_TYPE, "application/json");
+    for (name, value) in headers {
+        req = req.header(*name, *value);
+    }
+    let mut req = req
+        .body(Body::from(
+            json!({ "username": "alice", "password": PASSWORD }).to_string(),
+        ))
+        .unwrap();
+    let _prev_value = req
+        .extensions_mut()
+        .insert(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+
+    let res = app.request(req).await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT, "{}", res.text());
+
+    sqlx::query_scalar::<_, Option<Value>>(
+        r#"
+            SELECT details FROM audit_log
+            WHERE action = 'auth.login_succeeded'
+            ORDER BY audit_id DESC
+            LIMIT 1
+        "#,
+    )
+    .fetch_one(app.pg_pool())
+    .await
+    .unwrap()
+    .unwrap_or_default()
+}
+
+#[tokio::test]
+async fn the_peer_is_the_client_unless_it_is_a_trusted_proxy()
+{
+    let mut app = spawn().await;
+
+    let details = login_from(
+        &mut app,
+        "203.0.113.5:4711",
+        &[
+            ("x-forwarded-for", "198.51.100.7"),
+            ("fly-client-ip", "198.51.100.7"),
+            ("user-agent", "curl/8.0"),
+        ],
+    )
+    .await;
+
+    assert_eq!(details["ip"], "203.0.113.5");
+    assert_eq!(details["user_agent"], "curl/8.0");
+}
+
+#[tokio::test]
+async fn x_forwarded_for_is_walked_back_to_the_first_untrusted_hop()
+{
+    let mut app = spawn().await;
+
+    let details = login_from(
+        &mut app,
+        "10.0.0.1:4711",
+        &[("x-forwarded-for", "6.6.6.6, 198.51.100.7, 10.2.3.4")],
+    )
+    .await;
+
+    assert_eq!(details["ip"], "198.51.100.7");
+}
+
+#[tokio::test]
+async fn forwarded_is_understood()
+{
+    let mut app = spawn().await;
+
+    let details = login_from(
+        &mut app,
+        "[::ffff:10.0.0.1]:4711",
+        &[(
+            "forwarded",
+            r#"for=6.6.6.6, for="[2001:db8:cafe::17]:4711";proto=https, for=10.2.3.4"#,
+        )],
+    )
+    .await;
+
+    assert_eq!(details["ip"], "2001:db8:cafe::17");
+}
+
+#[tokio::test]
+async fn fly_client_ip_is_taken_from_a_trusted_proxy()
+{
+    let mut app = TestApp::builder()
+        .config("proxies.trusted", vec!["10.0.0.0/8", "fd00::/8"])
+        .config("proxies.fly", true)
+        .spawn()
+        .await;
+    app.register("alice", PASSWORD).await;
+
+    let details = login_from(
+        &mut app,
+        "[fd00::1]:4711",
+        &[
+            ("fly-client-ip", "198.51.100.7"),
+            ("x-forwarded-for", "6.6.6.6"),
+        ],
+    )
+    .await;
+
+    assert_eq!(details["ip"], "198.51.100.7");
+}
+
+#[tokio::test]
+async fn fly_client_ip_is_ignored_unless_the_proxies_are_fly()
+{
+    let mut app = spawn().await;
+
+    let details = login_from(
+        &mut app,
+        "[fd00::1]:4711",
+        &[
+            ("fly-client-ip", "198.51.100.7"),
+            ("x-forwarded-for", "6.6.6.6"),
+        ],
+    )
+    .await;
+
+    assert_eq!(details["ip"], "6.6.6.6");
+}
+
+#[tokio::test]
+async fn an_unknown_client_is_left_out()
+{
+    let mut app = spawn().await;
+
+    let details = login_from(&mut app, "10.0.0.1:4711", &[("forwarded", "for=unknown")]).await;
+
+    assert!(details["ip"].is_null(), "{details}");
+}
@@ -0,0 +1,171 @@
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use serde_json::json;
+
+mod common;
+use common::{TestApp, PASSWORD};
+
+fn post_auth(accept: &str, body: &'static str) -> Request<Body>
+{
+    Request::post("/v1/auth")
+        .header(header::ACCEPT, accept)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn errors_keep_the_original_shape_by_default()
+{
+    let mut app = TestApp::spawn().await;
+
+    let res = app.get("/v1/auth").await;
+
+    assert_eq!(res.headers[header::CONTENT_TYPE], "application/json");
+    let payload = res.json();
+    assert_eq!(payload["code"], 403);
+    assert_eq!(payload["message"], "must be authenticated");
+    assert!(payload["request_id"].is_string());
+    assert!(payload.get("errors").is_none());
+}
+
+#[tokio::test]
+async fn errors_are_problem_details_when_asked_for()
+{
+    let mut app = TestApp::spawn().await;
+
+    let res = app
+        .request(post_auth(
+            "application/problem+json, application/json;q=0.5",
+            r#"{"username":"nobody","password":"x"}"#,
+        ))
+        .await;
+
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    assert_eq!(
+        res.headers[header::CONTENT_TYPE],
+        "application/problem+json"
+    );
+    let payload = res.json();
+    assert_eq!(payload["type"], "/errors/401");
+    assert_eq!(payload["title"], "User Not Found");
+    assert_eq!(payload["status"], 404);
+    assert_eq!(payload["code"], 401);
+    assert_eq!(
+        payload["instance"],
+        res.headers["x-request-id"].to_str().unwrap()
+    );
+    assert_eq!(payload["errors"], json!([]));
+}
+
+#[tokio::test]
+async fn problem_details_can_be_refused()
+{
+    let mut app = TestApp::spawn().await;
+
+    let res = app
+        .request(post_auth(
+            "application/problem+json;q=0, application/json",
+            r#"{"username":"nobody","password":"x"}"#,
+        ))
+        .await;
+
+    assert_eq!(res.headers[header::CONTENT_TYPE], "application/json");
+    assert!(res.json().get("type").is_none());
+}
+
+#[tokio::test]
+async fn json_data_errors_name_the_field()
+{
+    let mut app = TestApp::spawn().await;
+
+    let res = app
+        .request(post_auth(
+            "application/problem+json",
+            r#"{"username":"alice","password":1}"#,
+        ))
+        .await;
+
+    res.assert_error(StatusCode::UNPROCESSABLE_ENTITY, 110);
+    assert_eq!(
+        res.json()["errors"],
+        json!([{
+            "field": "password",
+            "message": "invalid type: integer `1`, expected a string"
+        }])
+    );
+}
+
+#[tokio::test]
+async fn json_data_errors_name_a_missing_field()
+{
+    let mut app = TestApp::spawn().await;
+
+    let res = app
+        .post_json("/v1/auth", json!({ "username": "alice" }))
+        .await;
+
+    res.assert_error(StatusCode::UNPROCESSABLE_ENTITY, 110);
+    assert_eq!(
+        res.json()["errors"],
+        json!([{ "field": "password", "message": "missing field `password`" }])
+    );
+}
+
+#[tokio::test]
+async fn a_taken_username_is_a_field_error()
+{
+    let mut app = TestApp::spawn().await;
+    app.register("alice", PASSWORD).await;
+
+    let res = app
+        .post_json(
+            "/v1/users",
+            json!({ "username": "alice", "password": PASSWORD }),
+        )
+        .await;
+
+    res.assert_error(StatusCode::CONFLICT, 501);
+    assert_eq!(res.json()["errors"][0]["field"], "username");
+}
+
+#[tokio::test]
+async fn the_error_catalog_lists_every_code()
+{
+    let mut app = TestApp::spawn().await;
+
+    let res = app.get("/errors").await;
+
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let payload = res.json();
+    let codes = payload["codes"].as_array().unwrap();
+    assert!(codes
+        .windows(2)
+        .all(|pair| pair[0]["code"].as_u64() < pair[1]["code"].as_u64()));
+    let user_not_found = codes.iter().find(|code| code["code"] == 401).unwrap();
+    assert_eq!(user_not_found["status"], 404);
+    assert_eq!(user_not_found["category"], "auth");
+    assert_eq!(user_not_found["title"], "User Not Found");
+    assert!(user_not_found["message"].is_string());
+}
+
+#[tokio::test]
+async fn the_error_catalog_describes_a_single_code()
+{
+    let mut app = TestApp::spawn().await;
+
+    let res = app.get("/errors/501").await;
+
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let payload = res.json();
+    assert_eq!(payload["code"], 501);
+    assert_eq!(payload["status"], 409);
+    assert_eq!(payload["category"], "users");
+
+    assert_eq!(app.get("/errors/1234").await.status, StatusCode::NOT_FOUND);
+    app.get("/errors/abc")
+        .await
+        .assert_error(StatusCode::BAD_REQUEST, 301);
+}
@@ -0,0 +1,106 @@
+use std::time::Duration;
+
+use axum::http::StatusCode;
+use serde_json::json;
+
+mod common;
+use common::{TestApp, PASSWORD};
+
+#[tokio::test]
+async fn credentials_bodies_are_kept_small()
+{
+    let mut app = TestApp::builder()
+        .config("limits.credentials_body_bytes", 64)
+        .spawn()
+        .await;
+
+    let res = app
+        .post_json(
+            "/v1/auth",
+            json!({ "username": "a".repeat(64), "password": PASSWORD }),
+        )
+        .await;
+
+    res.assert_error(StatusCode::PAYLOAD_TOO_LARGE, 130);
+}
+
+#[tokio::test]
+async fn credentials_bodies_within_the_limit_are_read()
+{
+    let mut app = TestApp::builder()
+        .config("limits.credentials_body_bytes", 128)
+        .spawn()
+        .await;
+
+    let res = app
+        .post_json(
+            "/v1/users",
+            json!({ "username": "alice", "password": PASSWORD }),
+        )
+        .await;
+
+    assert_eq!(res.status, StatusCode::NO_CONTENT, "{}", res.text());
+}
+
+#[tokio::test]
+async fn requests_taking_too_long_time_out()
+{
+    let app = TestApp::builder()
+        .config("limits.request_timeout_secs", 1)
+        .spawn()
+        .await;
+
+    let (_sender, res) = app.start_stalled_post("/v1/auth");
+    let res = tokio::time::timeout(Duration::from_secs(5), res)
+        .await
+        .unwrap()
+        .unwrap();
+
+    res.assert_error(StatusCode::SERVICE_UNAVAILABLE, 901);
+}
+
+#[tokio::test]
+async fn requests_beyond_the_concurrency_limit_are_shed()
+{
+    let mut app = TestApp::builder()
+        .config("limits.max_concurrent_requests", 1)
+        .spawn()
+        .await;
+
+    let (sender, stalled) = app.start_stalled_post("/v1/auth");
+    // Lets the stalled request take the only slot
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+
+    let res = app.get("/v1/auth").await;
+    res.assert_error(StatusCode::SERVICE_UNAVAILABLE, 902);
+
+    // Aborting the body frees the slot up again
+    sender.abort();
+    let _stalled = stalled.await.unwrap();
+
+    let res = app.get("/v1/auth").await;
+    res.assert_error(StatusCode::UNAUTHORIZED, 403);
+}
+
+#[tokio::test]
+async fn health_checks_are_not_shed()
+{
+    let mut app = TestApp::builder()
+        .config("limits.max_concurrent_requests", 1)
+        .spawn()
+        .await;
+
+    let (_sender, _stalled) = app.start_stalled_post("/v1/auth");
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+    app.get("/v1/auth")
+        .await
+        .assert_error(StatusCode::SERVICE_UNAVAILABLE, 902);
+
+    let res = app.get("/healthz").await;
+
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+}
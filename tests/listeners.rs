@@ -0,0 +1,165 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use tokio::net::{TcpStream, UnixStream};
+
+mod common;
+use common::{free_port, send, TestApp, TestResponse, TestServer};
+
+struct Listeners
+{
+    port: u16,
+    admin_port: u16,
+    unix_socket: PathBuf,
+    admin_unix_socket: PathBuf,
+    dir: PathBuf,
+    _server: TestServer,
+}
+
+impl Listeners
+{
+    async fn serve() -> Self
+    {
+        let dir = env::temp_dir().join(format!("mindtrails_sock_{:016x}", rand::random::<u64>()));
+        fs::create_dir_all(&dir).unwrap();
+        let unix_socket = dir.join("api.sock");
+        let admin_unix_socket = dir.join("admin.sock");
+
+        let port = free_port();
+        let admin_port = free_port();
+        let server = TestApp::builder()
+            .config("server.port", i64::from(port))
+            .config("server.unix_socket", unix_socket.to_str().unwrap())
+            .config("admin.port", i64::from(admin_port))
+            .config("admin.unix_socket", admin_unix_socket.to_str().unwrap())
+            .serve()
+            .await;
+
+        Listeners {
+            port,
+            admin_port,
+            unix_socket,
+            admin_unix_socket,
+            dir,
+            _server: server,
+        }
+    }
+
+    async fn get_tcp(&self, port: u16, path: &str) -> TestResponse
+    {
+        let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        send(stream, false, get(path)).await
+    }
+
+    async fn get_unix(&self, unix_socket: &Path, path: &str) -> TestResponse
+    {
+        let stream = UnixStream::connect(unix_socket).await.unwrap();
+        send(stream, false, get(path)).await
+    }
+}
+
+impl Drop for Listeners
+{
+    fn drop(&mut self)
+    {
+        let _removed = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn get(path: &str) -> Request<Body>
+{
+    Request::get(path)
+        .header(header::HOST, "localhost")
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn the_admin_api_is_only_served_on_the_admin_listeners()
+{
+    let listeners = Listeners::serve().await;
+
+    let res = listeners.get_tcp(listeners.port, "/v1/admin/users").await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+    let res = listeners
+        .get_unix(&listeners.unix_socket, "/v1/admin/users")
+        .await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+
+    // Refused for want of a session rather than missing
+    let res = listeners
+        .get_tcp(listeners.admin_port, "/v1/admin/users")
+        .await;
+    res.assert_error(StatusCode::UNAUTHORIZED, 403);
+    let res = listeners
+        .get_unix(&listeners.admin_unix_socket, "/v1/admin/users")
+        .await;
+    res.assert_error(StatusCode::UNAUTHORIZED, 403);
+}
+
+#[tokio::test]
+async fn metrics_are_only_served_on_the_admin_listeners()
+{
+    let listeners = Listeners::serve().await;
+
+    let res = listeners.get_tcp(listeners.port, "/metrics").await;
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+
+    let res = listeners.get_tcp(listeners.admin_port, "/metrics").await;
+    assert_eq!(res.status, StatusCode::OK);
+    let res = listeners
+        .get_unix(&listeners.admin_unix_socket, "/metrics")
+        .await;
+    assert_eq!(res.status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn the_api_and_health_checks_are_served_on_every_listener()
+{
+    let listeners = Listeners::serve().await;
+
+    for res in [
+        listeners.get_tcp(listeners.port, "/healthz").await,
+        listeners.get_unix(&listeners.unix_socket, "/healthz").await,
+        listeners.get_tcp(listeners.admin_port, "/healthz").await,
+        listeners
+            .get_unix(&listeners.admin_unix_socket, "/healthz")
+            .await,
+    ] {
+        assert_eq!(res.status, StatusCode::NO_CONTENT);
+    }
+
+    let res = listeners.get_unix(&listeners.unix_socket, "/v1/auth").await;
+    res.assert_error(StatusCode::UNAUTHORIZED, 403);
+}
+
+#[tokio::test]
+async fn without_an_admin_listener_everything_is_served_together()
+{
+    let mut app = TestApp::spawn().await;
+
+    let res = app.get("/v1/admin/users").await;
+
+    res.assert_error(StatusCode::UNAUTHORIZED, 403);
+}
+
+#[tokio::test]
+async fn without_an_admin_listener_metrics_are_not_served()
+{
+    let port = free_port();
+    let _server = TestApp::builder()
+        .config("server.port", i64::from(port))
+        .serve()
+        .await;
+
+    let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    let res = send(stream, false, get("/metrics")).await;
+
+    assert_eq!(res.status, StatusCode::NOT_FOUND);
+}
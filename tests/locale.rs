@@ -0,0 +1,176 @@
+use axum::{
+    body::{Body, Bytes},
+    http::{header, Method, Request, StatusCode},
+};
+use serde_json::{json, Value};
+
+mod common;
+use common::{TestApp, TestResponse, PASSWORD};
+
+async fn get(app: &mut TestApp, path: &str, accept_language: &str) -> TestResponse
+{
+    app.request(
+        Request::get(path)
+            .header(header::ACCEPT_LANGUAGE, accept_language)
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await
+}
+
+async fn set_locale(app: &mut TestApp, locale: Value)
+{
+    let res = app
+        .request(
+            Request::put("/v1/users/locale")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(json!({ "locale": locale }).to_string()))
+                .unwrap(),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT, "{}", res.text());
+}
+
+#[tokio::test]
+async fn errors_are_in_english_by_default()
+{
+    let mut app = TestApp::spawn().await;
+
+    let res = app.get("/v1/auth").await;
+
+    assert_eq!(res.headers[header::CONTENT_LANGUAGE], "en");
+    assert_eq!(res.json()["message"], "must be authenticated");
+}
+
+#[tokio::test]
+async fn errors_follow_accept_language()
+{
+    let mut app = TestApp::spawn().await;
+
+    let res = get(&mut app, "/v1/auth", "fr-CA, en;q=0.8").await;
+
+    res.assert_error(StatusCode::UNAUTHORIZED, 403);
+    assert_eq!(res.headers[header::CONTENT_LANGUAGE], "fr");
+    assert_eq!(res.json()["message"], "authentification requise");
+    assert!(res
+        .headers
+        .get_all(header::VARY)
+        .iter()
+        .any(|vary| vary == "accept-language"));
+}
+
+#[tokio::test]
+async fn accept_language_weights_are_honoured()
+{
+    let mut app = TestApp::spawn().await;
+
+    let res = get(&mut app, "/v1/auth", "de;q=0.5, es, fr;q=0").await;
+    assert_eq!(res.headers[header::CONTENT_LANGUAGE], "es");
+
+    let res = get(&mut app, "/v1/auth", "fr;q=0, ja").await;
+    assert_eq!(res.headers[header::CONTENT_LANGUAGE], "en");
+}
+
+#[tokio::test]
+async fn errors_follow_the_users_preference()
+{
+    let mut app = TestApp::spawn().await;
+    let user_id = app.register("alice", PASSWORD).await;
+    app.login("alice", PASSWORD).await;
+
+    set_locale(&mut app, json!("de")).await;
+    let res = get(&mut app, &format!("/v1/users/{user_id}"), "fr").await;
+
+    res.assert_error(StatusCode::FORBIDDEN, 404);
+    assert_eq!(res.headers[header::CONTENT_LANGUAGE], "de");
+
+    // The preference outlives the session
+    assert_eq!(app.delete("/v1/auth").await.status, StatusCode::NO_CONTENT);
+    app.login("alice", PASSWORD).await;
+    let res = app.get(&format!("/v1/users/{user_id}")).await;
+
+    assert_eq!(
+        res.json()["message"],
+        "die für diese Aktion erforderliche Berechtigung fehlt"
+    );
+
+    set_locale(&mut app, Value::Null).await;
+    let res = get(&mut app, &format!("/v1/users/{user_id}"), "es").await;
+
+    assert_eq!(res.headers[header::CONTENT_LANGUAGE], "es");
+}
+
+#[tokio::test]
+async fn set_locale_requires_a_session()
+{
+    let mut app = TestApp::spawn().await;
+
+    let res = app
+        .request(
+            Request::put("/v1/users/locale")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"locale":"fr"}"#))
+                .unwrap(),
+        )
+        .await;
+
+    res.assert_error(StatusCode::UNAUTHORIZED, 403);
+}
+
+#[tokio::test]
+async fn set_locale_does_not_bring_back_a_revoked_session()
+{
+    let mut app = TestApp::spawn().await;
+    let user_id = app.register("alice", PASSWORD).await;
+    app.login("alice", PASSWORD).await;
+    let cookies = app.take_cookies();
+
+    // The session is loaded before the body is read. The second chunk is
+    // only taken once the first has been read, by then the session is.
+    app.set_cookies(cookies.clone());
+    let (mut sender, res) = app.start_stalled(Method::PUT, "/v1/users/locale");
+    sender
+        .send_data(Bytes::from(r#"{"locale":"#))
+        .await
+        .unwrap();
+    sender.send_data(Bytes::from(r#""fr"}"#)).await.unwrap();
+
+    let logout = app.delete("/v1/auth").await;
+    assert_eq!(logout.status, StatusCode::NO_CONTENT);
+    drop(sender);
+
+    res.await
+        .unwrap()
+        .assert_error(StatusCode::UNAUTHORIZED, 403);
+    app.set_cookies(cookies);
+    app.get("/v1/auth")
+        .await
+        .assert_error(StatusCode::BAD_REQUEST, 201);
+    let locale =
+        sqlx::query_scalar::<_, Option<String>>("SELECT locale FROM users WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_one(app.pg_pool())
+            .await
+            .unwrap();
+    assert_eq!(locale, None);
+}
+
+#[tokio::test]
+async fn set_locale_rejects_an_unknown_locale()
+{
+    let mut app = TestApp::spawn().await;
+    app.register("alice", PASSWORD).await;
+    app.login("alice", PASSWORD).await;
+
+    let res = app
+        .request(
+            Request::put("/v1/users/locale")
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(r#"{"locale":"xx"}"#))
+                .unwrap(),
+        )
+        .await;
+
+    res.assert_error(StatusCode::UNPROCESSABLE_ENTITY, 110);
+    assert_eq!(res.json()["errors"][0]["field"], "locale");
+}
@@ -0,0 +1,33 @@
+//! The frontend generates its client from the committed `openapi.json`, so it
+//! has to match what the app serves
+
+use std::{env, fs};
+
+use axum::http::StatusCode;
+
+mod common;
+use common::TestApp;
+
+const SPEC_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.json");
+
+#[tokio::test]
+async fn the_committed_spec_is_up_to_date()
+{
+    let mut app = TestApp::spawn().await;
+
+    let res = app.get("/openapi.json").await;
+
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    let spec = serde_json::to_string_pretty(&res.json()).unwrap() + "\n";
+
+    if env::var_os("UPDATE_OPENAPI").is_some() {
+        fs::write(SPEC_PATH, spec).unwrap();
+        return;
+    }
+
+    let committed = fs::read_to_string(SPEC_PATH).unwrap_or_default();
+    assert!(
+        committed == spec,
+        "openapi.json is out of date, regenerate it with `UPDATE_OPENAPI=1 cargo test --test openapi`"
+    );
+}
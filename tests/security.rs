@@ -0,0 +1,156 @@
+use std::net::SocketAddr;
+
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{header, HeaderName, Request, StatusCode},
+};
+use serde_json::json;
+
+mod common;
+use common::{TestApp, TestResponse, PASSWORD};
+
+/// Logs in as alice, from wherever `header` says the request was sent from
+async fn login_from(app: &mut TestApp, header: HeaderName, value: &str) -> TestResponse
+{
+    app.request(
+        Request::post("/v1/auth")
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header, value)
+            .body(Body::from(
+                json!({ "username": "alice", "password": PASSWORD }).to_string(),
+            ))
+            .unwrap(),
+    )
+    .await
+}
+
+#[tokio::test]
+async fn requests_from_an_allowed_origin_go_through()
+{
+    let mut app = TestApp::spawn().await;
+    app.register("alice", PASSWORD).await;
+
+    let res = login_from(&mut app, header::ORIGIN, "http://localhost").await;
+
+    assert_eq!(res.status, StatusCode::NO_CONTENT, "{}", res.text());
+}
+
+#[tokio::test]
+async fn requests_from_another_origin_are_refused()
+{
+    let mut app = TestApp::spawn().await;
+    app.register("alice", PASSWORD).await;
+
+    let res = login_from(&mut app, header::ORIGIN, "https://evil.example").await;
+
+    res.assert_error(StatusCode::FORBIDDEN, 411);
+    assert!(res.headers.get(header::SET_COOKIE).is_none());
+}
+
+#[tokio::test]
+async fn requests_from_an_opaque_origin_are_refused()
+{
+    let mut app = TestApp::spawn().await;
+
+    let res = login_from(&mut app, header::ORIGIN, "null").await;
+
+    res.assert_error(StatusCode::FORBIDDEN, 411);
+}
+
+#[tokio::test]
+async fn the_referer_is_checked_without_an_origin()
+{
+    let mut app = TestApp::spawn().await;
+    app.register("alice", PASSWORD).await;
+
+    let res = login_from(&mut app, header::REFERER, "https://evil.example/login").await;
+    res.assert_error(StatusCode::FORBIDDEN, 411);
+
+    let res = login_from(&mut app, header::REFERER, "http://localhost/login?next=/").await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT, "{}", res.text());
+}
+
+#[tokio::test]
+async fn safe_requests_are_not_checked()
+{
+    let mut app = TestApp::spawn().await;
+
+    let res = app
+        .request(
+            Request::get("/v1/auth")
+                .header(header::ORIGIN, "https://evil.example")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await;
+
+    res.assert_error(StatusCode::UNAUTHORIZED, 403);
+}
+
+#[tokio::test]
+async fn responses_carry_the_security_headers()
+{
+    let mut app = TestApp::spawn().await;
+
+    let res = app.get("/v1/auth").await;
+
+    assert_eq!(res.headers[header::X_CONTENT_TYPE_OPTIONS], "nosniff");
+    assert_eq!(res.headers[header::REFERRER_POLICY], "no-referrer");
+    assert_eq!(
+        res.headers[header::CONTENT_SECURITY_POLICY],
+        "frame-ancestors 'none'"
+    );
+    // Only sent in production unless configured, and over HTTPS
+    assert!(res.headers.get(header::STRICT_TRANSPORT_SECURITY).is_none());
+}
+
+#[tokio::test]
+async fn security_headers_can_be_configured()
+{
+    let mut app = TestApp::builder()
+        .config("security_headers.referrer_policy", "same-origin")
+        .config(
+            "security_headers.frame_ancestors",
+            "'self' https://example.com",
+        )
+        .spawn()
+        .await;
+
+    let res = app.get("/healthz").await;
+
+    assert_eq!(res.headers[header::REFERRER_POLICY], "same-origin");
+    assert_eq!(
+        res.headers[header::CONTENT_SECURITY_POLICY],
+        "frame-ancestors 'self' https://example.com"
+    );
+}
+
+#[tokio::test]
+async fn hsts_is_only_sent_over_https()
+{
+    let mut app = TestApp::builder()
+        .config("proxies.trusted", vec!["10.0.0.0/8"])
+        .config("security_headers.hsts_max_age_secs", 600)
+        .config("security_headers.hsts_include_subdomains", true)
+        .spawn()
+        .await;
+
+    let mut req = Request::get("/healthz")
+        .header("x-forwarded-proto", "https")
+        .body(Body::empty())
+        .unwrap();
+    let _prev_value = req
+        .extensions_mut()
+        .insert(ConnectInfo(SocketAddr::from(([10, 0, 0, 1], 4711))));
+    let res = app.request(req).await;
+
+    assert_eq!(
+        res.headers[header::STRICT_TRANSPORT_SECURITY],
+        "max-age=600; includeSubDomains"
+    );
+
+    let res = app.get("/healthz").await;
+
+    assert!(res.headers.get(header::STRICT_TRANSPORT_SECURITY).is_none());
+}
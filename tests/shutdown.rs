@@ -0,0 +1,103 @@
+use std::time::{Duration, Instant};
+
+use axum::{
+    body::{Body, Bytes},
+    http::{header, Request, StatusCode},
+};
+use hyper::body::Sender;
+use tokio::{net::TcpStream, task::JoinHandle};
+
+mod common;
+use common::{free_port, send, TestApp, TestResponse, TestServer};
+
+async fn serve(grace_period_secs: i64, drain_timeout_secs: i64) -> (TestServer, u16)
+{
+    let port = free_port();
+    let server = TestApp::builder()
+        .config("server.port", i64::from(port))
+        .config("shutdown.grace_period_secs", grace_period_secs)
+        .config("shutdown.drain_timeout_secs", drain_timeout_secs)
+        .serve()
+        .await;
+
+    (server, port)
+}
+
+/// Starts creating a user without sending the whole body yet, which leaves
+/// the request in flight until `Sender` sends the rest
+async fn start_request(port: u16) -> (Sender, JoinHandle<TestResponse>)
+{
+    let (mut body, req_body) = Body::channel();
+    let req = Request::post("/v1/users")
+        .header(header::HOST, "localhost")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(req_body)
+        .unwrap();
+
+    let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    let res = tokio::spawn(send(stream, false, req));
+    body.send_data(Bytes::from_static(b"{")).await.unwrap();
+
+    // Gives the server time to start handling it
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    (body, res)
+}
+
+#[tokio::test]
+async fn readiness_fails_during_the_grace_period()
+{
+    let (mut server, port) = serve(2, 1).await;
+
+    server.shut_down();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // Still accepting connections meanwhile
+    let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    let req = Request::get("/readyz")
+        .header(header::HOST, "localhost")
+        .body(Body::empty())
+        .unwrap();
+    let res = send(stream, false, req).await;
+
+    assert_eq!(res.status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(res.json()["ready"], false);
+    assert_eq!(res.json()["shutting_down"], true);
+
+    server.stopped().await.unwrap();
+}
+
+#[tokio::test]
+async fn requests_in_flight_are_drained()
+{
+    let (mut server, port) = serve(0, 5).await;
+    let (body, res) = start_request(port).await;
+
+    server.shut_down();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    // No longer accepting connections, but still handling the request
+    assert!(TcpStream::connect(("127.0.0.1", port)).await.is_err());
+    drop(body);
+
+    res.await
+        .unwrap()
+        .assert_error(StatusCode::BAD_REQUEST, 100);
+    server.stopped().await.unwrap();
+}
+
+#[tokio::test]
+async fn requests_left_at_the_drain_timeout_are_cut_off()
+{
+    let (mut server, port) = serve(0, 1).await;
+    let (_body, res) = start_request(port).await;
+
+    let started = Instant::now();
+    server.shut_down();
+
+    res.await
+        .unwrap()
+        .assert_error(StatusCode::SERVICE_UNAVAILABLE, 903);
+    server.stopped().await.unwrap();
+    assert!(started.elapsed() < Duration::from_secs(3));
+}
@@ -0,0 +1,212 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    body::Body,
+    http::{header, Request, StatusCode},
+};
+use tokio::net::TcpStream;
+use tokio_rustls::{
+    client::TlsStream,
+    rustls::{Certificate, ClientConfig, RootCertStore, ServerName},
+    TlsConnector,
+};
+
+mod common;
+use common::{free_port, send, TestApp, TestServer};
+
+/// A self-signed certificate for `localhost`, written where the config can
+/// point to it
+struct TestCertificate
+{
+    der: Vec<u8>,
+    cert_path: PathBuf,
+    key_path: PathBuf,
+}
+
+impl TestCertificate
+{
+    fn generate(dir: &Path) -> Self
+    {
+        let cert = rcgen::generate_simple_self_signed(vec![String::from("localhost")]).unwrap();
+
+        // Every serialization is signed anew, so the DER has to come from
+        // the very PEM the server reads
+        let pem = cert.serialize_pem().unwrap();
+        let certificate = TestCertificate {
+            der: rustls_pemfile::certs(&mut pem.as_bytes())
+                .unwrap()
+                .remove(0),
+            cert_path: dir.join("cert.pem"),
+            key_path: dir.join("key.pem"),
+        };
+        fs::write(&certificate.cert_path, pem).unwrap();
+        fs::write(&certificate.key_path, cert.serialize_private_key_pem()).unwrap();
+
+        certificate
+    }
+}
+
+struct TlsServer
+{
+    port: u16,
+    redirect_port: u16,
+    certificate: TestCertificate,
+    dir: PathBuf,
+    _server: TestServer,
+}
+
+impl TlsServer
+{
+    async fn serve() -> Self
+    {
+        let dir = env::temp_dir().join(format!("mindtrails_tls_{:016x}", rand::random::<u64>()));
+        fs::create_dir_all(&dir).unwrap();
+        let certificate = TestCertificate::generate(&dir);
+
+        let port = free_port();
+        let redirect_port = free_port();
+        let server = TestApp::builder()
+            .config("server.port", i64::from(port))
+            .config("tls.cert_path", certificate.cert_path.to_str().unwrap())
+            .config("tls.key_path", certificate.key_path.to_str().unwrap())
+            .config("tls.redirect_port", i64::from(redirect_port))
+            .config("tls.reload_interval_secs", 1)
+            .config("security_headers.hsts_max_age_secs", 600)
+            .serve()
+            .await;
+
+        TlsServer {
+            port,
+            redirect_port,
+            certificate,
+            dir,
+            _server: server,
+        }
+    }
+
+    /// Connects over TLS, trusting only `roots` and offering `alpn`
+    async fn connect(&self, roots: &[&[u8]], alpn: &[&[u8]]) -> TlsStream<TcpStream>
+    {
+        let mut root_store = RootCertStore::empty();
+        for root in roots {
+            root_store.add(&Certificate(root.to_vec())).unwrap();
+        }
+
+        let mut client_config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(root_store)
+            .with_no_client_auth();
+        client_config.alpn_protocols = alpn.iter().map(|protocol| protocol.to_vec()).collect();
+
+        let stream = TcpStream::connect(("127.0.0.1", self.port)).await.unwrap();
+        TlsConnector::from(Arc::new(client_config))
+            .connect(ServerName::try_from("localhost").unwrap(), stream)
+            .await
+            .unwrap()
+    }
+}
+
+impl Drop for TlsServer
+{
+    fn drop(&mut self)
+    {
+        let _removed = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn healthz() -> Request<Body>
+{
+    Request::get("https://localhost/healthz")
+        .body(Body::empty())
+        .unwrap()
+}
+
+#[tokio::test]
+async fn https_is_served_with_http2_when_negotiated()
+{
+    let server = TlsServer::serve().await;
+
+    let stream = server
+        .connect(&[&server.certificate.der], &[b"h2", b"http/1.1"])
+        .await;
+    assert_eq!(stream.get_ref().1.alpn_protocol(), Some(&b"h2"[..]));
+
+    let res = send(stream, true, healthz()).await;
+
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+    // The connection itself is over HTTPS, without a proxy saying so
+    assert_eq!(
+        res.headers[header::STRICT_TRANSPORT_SECURITY],
+        "max-age=600"
+    );
+}
+
+#[tokio::test]
+async fn https_is_served_with_http1_otherwise()
+{
+    let server = TlsServer::serve().await;
+
+    let stream = server
+        .connect(&[&server.certificate.der], &[b"http/1.1"])
+        .await;
+    assert_eq!(stream.get_ref().1.alpn_protocol(), Some(&b"http/1.1"[..]));
+
+    let res = send(stream, false, healthz()).await;
+
+    assert_eq!(res.status, StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn plain_http_is_redirected_to_https()
+{
+    let server = TlsServer::serve().await;
+
+    let stream = TcpStream::connect(("127.0.0.1", server.redirect_port))
+        .await
+        .unwrap();
+    let req = Request::get("/v1/users?limit=5")
+        .header(header::HOST, format!("localhost:{}", server.redirect_port))
+        .body(Body::empty())
+        .unwrap();
+    let res = send(stream, false, req).await;
+
+    assert_eq!(res.status, StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(
+        res.headers[header::LOCATION],
+        format!("https://localhost:{}/v1/users?limit=5", server.port)
+    );
+}
+
+#[tokio::test]
+async fn the_certificate_is_reloaded_when_its_files_change()
+{
+    let server = TlsServer::serve().await;
+    let old_der = server.certificate.der.clone();
+
+    let new_certificate = TestCertificate::generate(&server.dir);
+    let roots: [&[u8]; 2] = [&old_der, &new_certificate.der];
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let stream = server.connect(&roots, &[b"http/1.1"]).await;
+        let served = stream.get_ref().1.peer_certificates().unwrap()[0].0.clone();
+        if served == new_certificate.der {
+            // The new certificate works for requests as much as the old one
+            let res = send(stream, false, healthz()).await;
+            assert_eq!(res.status, StatusCode::NO_CONTENT);
+            break;
+        }
+
+        assert_eq!(served, old_der);
+        assert!(
+            Instant::now() < deadline,
+            "the new certificate was never served"
+        );
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+}
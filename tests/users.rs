@@ -0,0 +1,396 @@
+use axum::http::StatusCode;
+use serde_json::json;
+use uuid::Uuid;
+
+mod common;
+use common::{TestApp, PASSWORD};
+
+const NEW_PASSWORD: &str = "another correct horse battery staple";
+
+#[tokio::test]
+async fn create_user_registers_an_account()
+{
+    let mut app = TestApp::spawn().await;
+
+    let res = app
+        .post_json(
+            "/v1/users",
+            json!({ "username": "alice", "password": PASSWORD }),
+        )
+        .await;
+
+    assert_eq!(res.status, StatusCode::NO_CONTENT, "{}", res.text());
+    app.login("alice", PASSWORD).await;
+}
+
+#[tokio::test]
+async fn create_user_rejects_a_taken_username()
+{
+    let mut app = TestApp::spawn().await;
+    app.register("alice", PASSWORD).await;
+
+    let res = app
+        .post_json(
+            "/v1/users",
+            json!({ "username": "alice", "password": PASSWORD }),
+        )
+        .await;
+
+    res.assert_error(StatusCode::CONFLICT, 501);
+}
+
+#[tokio::test]
+async fn create_user_rejects_a_breached_password()
+{
+    let mut app = TestApp::builder()
+        .breached_password("password1")
+        .spawn()
+        .await;
+
+    let res = app
+        .post_json(
+            "/v1/users",
+            json!({ "username": "alice", "password": "password1" }),
+        )
+        .await;
+
+    res.assert_error(StatusCode::UNPROCESSABLE_ENTITY, 502);
+    assert_eq!(res.json()["errors"][0]["field"], "password");
+}
+
+#[tokio::test]
+async fn create_user_warns_about_a_breached_password()
+{
+    let mut app = TestApp::builder()
+        .breached_password("password1")
+        .config("breached_passwords.policy", "warn")
+        .spawn()
+        .await;
+
+    let res = app
+        .post_json(
+            "/v1/users",
+            json!({ "username": "alice", "password": "password1" }),
+        )
+        .await;
+
+    assert_eq!(res.status, StatusCode::NO_CONTENT, "{}", res.text());
+    assert_eq!(res.headers["x-password-breached"], "42");
+    app.login("alice", "password1").await;
+}
+
+#[tokio::test]
+async fn create_user_is_refused_when_registration_is_disabled()
+{
+    let mut app = TestApp::builder()
+        .config("features.registration", false)
+        .spawn()
+        .await;
+
+    let res = app
+        .post_json(
+            "/v1/users",
+            json!({ "username": "alice", "password": PASSWORD }),
+        )
+        .await;
+
+    res.assert_error(StatusCode::FORBIDDEN, 503);
+}
+
+#[tokio::test]
+async fn create_user_rejects_malformed_json()
+{
+    let mut app = TestApp::spawn().await;
+
+    app.post_raw("/v1/users", Some("application/json"), "{")
+        .await
+        .assert_error(StatusCode::BAD_REQUEST, 100);
+    app.post_json("/v1/users", json!({ "username": "alice" }))
+        .await
+        .assert_error(StatusCode::UNPROCESSABLE_ENTITY, 110);
+    app.post_raw("/v1/users", None, r#"{"username":"alice","password":"x"}"#)
+        .await
+        .assert_error(StatusCode::UNSUPPORTED_MEDIA_TYPE, 120);
+}
+
+#[tokio::test]
+async fn change_password_replaces_the_password()
+{
+    let mut app = TestApp::spawn().await;
+    app.register("alice", PASSWORD).await;
+
+    let res = app
+        .post_json(
+            "/v1/users/password",
+            json!({ "username": "alice", "password": PASSWORD, "new_password": NEW_PASSWORD }),
+        )
+        .await;
+
+    assert_eq!(res.status, StatusCode::NO_CONTENT, "{}", res.text());
+    app.post_json(
+        "/v1/auth",
+        json!({ "username": "alice", "password": PASSWORD }),
+    )
+    .await
+    .assert_error(StatusCode::UNPROCESSABLE_ENTITY, 402);
+    app.login("alice", NEW_PASSWORD).await;
+}
+
+#[tokio::test]
+async fn change_password_destroys_existing_sessions()
+{
+    let mut app = TestApp::spawn().await;
+    app.register("alice", PASSWORD).await;
+    app.login("alice", PASSWORD).await;
+    let cookies = app.take_cookies();
+
+    let res = app
+        .post_json(
+            "/v1/users/password",
+            json!({ "username": "alice", "password": PASSWORD, "new_password": NEW_PASSWORD }),
+        )
+        .await;
+    assert_eq!(res.status, StatusCode::NO_CONTENT, "{}", res.text());
+    app.set_cookies(cookies);
+
+    let res = app.get("/v1/auth").await;
+
+    res.assert_error(StatusCode::BAD_REQUEST, 201);
+}
+
+#[tokio::test]
+async fn change_password_clears_a_pending_reset()
+{
+    let mut app = TestApp::spawn().await;
+    let user_id = app.register("alice", PASSWORD).await;
+    let _pg_query_res =
+        sqlx::query("UPDATE users SET password_reset_required = true WHERE user_id = $1")
+            .bind(user_id)
+            .execute(app.pg_pool())
+            .await
+            .unwrap();
+
+    let res = app
+        .post_json(
+            "/v1/users/password",
+            json!({ "username": "alice", "password": PASSWORD, "new_password": NEW_PASSWORD }),
+        )
+        .await;
+
+    assert_eq!(res.status, StatusCode::NO_CONTENT, "{}", res.text());
+    app.login("alice", NEW_PASSWORD).await;
+}
+
+#[tokio::test]
+async fn change_password_rejects_an_unknown_user()
+{
+    let mut app = TestApp::spawn().await;
+
+    let res = app
+        .post_json(
+            "/v1/users/password",
+            json!({ "username": "nobody", "password": PASSWORD, "new_password": NEW_PASSWORD }),
+        )
+        .await;
+
+    res.assert_error(StatusCode::NOT_FOUND, 401);
+}
+
+#[tokio::test]
+async fn change_password_rejects_a_wrong_password()
+{
+    let mut app = TestApp::spawn().await;
+    let user_id = app.register("alice", PASSWORD).await;
+
+    let res = app
+        .post_json(
+            "/v1/users/password",
+            json!({ "username": "alice", "password": "wrong", "new_password": NEW_PASSWORD }),
+        )
+        .await;
+
+    res.assert_error(StatusCode::UNPROCESSABLE_ENTITY, 402);
+    // Audited like a failed login, as it's as good a way to guess passwords
+    let failed_logins = sqlx::query_scalar::<_, i64>(
+        "SELECT count(*) FROM audit_log WHERE action = 'auth.login_failed' AND target_id = $1",
+    )
+    .bind(user_id)
+    .fetch_one(app.pg_pool())
+    .await
+    .unwrap();
+    assert_eq!(failed_logins, 1);
+}
+
+#[tokio::test]
+async fn change_password_shares_the_login_attempt_limit()
+{
+    let mut app = TestApp::spawn().await;
+    app.register("alice", PASSWORD).await;
+
+    for _ in 0..3 {
+        app.post_json(
+            "/v1/auth",
+            json!({ "username": "alice", "password": "wrong" }),
+        )
+        .await
+        .assert_error(StatusCode::UNPROCESSABLE_ENTITY, 402);
+    }
+
+    let res = app
+        .post_json(
+            "/v1/users/password",
+            json!({ "username": "alice", "password": PASSWORD, "new_password": NEW_PASSWORD }),
+        )
+        .await;
+
+    res.assert_error(StatusCode::TOO_MANY_REQUESTS, 410);
+}
+
+#[tokio::test]
+async fn change_password_rejects_a_disabled_account()
+{
+    let mut app = TestApp::spawn().await;
+    let user_id = app.register("alice", PASSWORD).await;
+    let _pg_query_res = sqlx::query("UPDATE users SET disabled = true WHERE user_id = $1")
+        .bind(user_id)
+        .execute(app.pg_pool())
+        .await
+        .unwrap();
+
+    let res = app
+        .post_json(
+            "/v1/users/password",
+            json!({ "username": "alice", "password": PASSWORD, "new_password": NEW_PASSWORD }),
+        )
+        .await;
+
+    res.assert_error(StatusCode::FORBIDDEN, 405);
+}
+
+#[tokio::test]
+async fn change_password_rejects_a_breached_password()
+{
+    let mut app = TestApp::builder()
+        .breached_password("password1")
+        .spawn()
+        .await;
+    app.register("alice", PASSWORD).await;
+
+    let res = app
+        .post_json(
+            "/v1/users/password",
+            json!({ "username": "alice", "password": PASSWORD, "new_password": "password1" }),
+        )
+        .await;
+
+    res.assert_error(StatusCode::UNPROCESSABLE_ENTITY, 502);
+    assert_eq!(res.json()["errors"][0]["field"], "new_password");
+}
+
+#[tokio::test]
+async fn change_password_warns_about_a_breached_password()
+{
+    let mut app = TestApp::builder()
+        .breached_password("password1")
+        .config("breached_passwords.policy", "warn")
+        .spawn()
+        .await;
+    app.register("alice", PASSWORD).await;
+
+    let res = app
+        .post_json(
+            "/v1/users/password",
+            json!({ "username": "alice", "password": PASSWORD, "new_password": "password1" }),
+        )
+        .await;
+
+    assert_eq!(res.status, StatusCode::NO_CONTENT, "{}", res.text());
+    assert_eq!(res.headers["x-password-breached"], "42");
+}
+
+#[tokio::test]
+async fn fetch_user_returns_the_user_and_their_roles()
+{
+    let mut app = TestApp::spawn().await;
+    let user_id = app.register("alice", PASSWORD).await;
+    let researcher_id = app.register("researcher", PASSWORD).await;
+    app.grant_role(researcher_id, "researcher").await;
+    app.login("researcher", PASSWORD).await;
+
+    let res = app.get(&format!("/v1/users/{user_id}")).await;
+
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(
+        res.json(),
+        json!({ "user_id": user_id, "username": "alice", "roles": [] })
+    );
+
+    let res = app.get(&format!("/v1/users/{researcher_id}")).await;
+
+    assert_eq!(res.json()["roles"], json!(["researcher"]));
+}
+
+#[tokio::test]
+async fn fetch_user_requires_a_session()
+{
+    let mut app = TestApp::spawn().await;
+    let user_id = app.register("alice", PASSWORD).await;
+
+    let res = app.get(&format!("/v1/users/{user_id}")).await;
+
+    res.assert_error(StatusCode::UNAUTHORIZED, 403);
+}
+
+#[tokio::test]
+async fn fetch_user_requires_the_permission()
+{
+    let mut app = TestApp::spawn().await;
+    let user_id = app.register("alice", PASSWORD).await;
+    app.login("alice", PASSWORD).await;
+
+    let res = app.get(&format!("/v1/users/{user_id}")).await;
+
+    res.assert_error(StatusCode::FORBIDDEN, 404);
+}
+
+#[tokio::test]
+async fn fetch_user_applies_a_granted_permission_to_existing_sessions()
+{
+    let mut app = TestApp::spawn().await;
+    let user_id = app.register("alice", PASSWORD).await;
+    app.login("alice", PASSWORD).await;
+    app.get(&format!("/v1/users/{user_id}"))
+        .await
+        .assert_error(StatusCode::FORBIDDEN, 404);
+
+    app.grant_role(user_id, "researcher").await;
+    let res = app.get(&format!("/v1/users/{user_id}")).await;
+
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+}
+
+#[tokio::test]
+async fn fetch_user_rejects_an_unknown_user()
+{
+    let mut app = TestApp::spawn().await;
+    let researcher_id = app.register("researcher", PASSWORD).await;
+    app.grant_role(researcher_id, "researcher").await;
+    app.login("researcher", PASSWORD).await;
+
+    let res = app.get(&format!("/v1/users/{}", Uuid::nil())).await;
+
+    res.assert_error(StatusCode::NOT_FOUND, 401);
+}
+
+#[tokio::test]
+async fn fetch_user_rejects_an_invalid_id()
+{
+    let mut app = TestApp::spawn().await;
+    let researcher_id = app.register("researcher", PASSWORD).await;
+    app.grant_role(researcher_id, "researcher").await;
+    app.login("researcher", PASSWORD).await;
+
+    let res = app.get("/v1/users/not-a-uuid").await;
+
+    res.assert_error(StatusCode::BAD_REQUEST, 301);
+}
@@ -0,0 +1,80 @@
+use axum::http::{header, StatusCode};
+
+mod common;
+use common::{TestApp, PASSWORD};
+
+#[tokio::test]
+async fn the_current_version_is_not_deprecated()
+{
+    let mut app = TestApp::spawn().await;
+
+    let res = app.get("/v1/auth").await;
+
+    res.assert_error(StatusCode::UNAUTHORIZED, 403);
+    assert!(res.headers.get("deprecation").is_none());
+    assert!(res.headers.get("sunset").is_none());
+}
+
+#[tokio::test]
+async fn unversioned_paths_are_deprecated_aliases()
+{
+    let mut app = TestApp::spawn().await;
+    let user_id = app.register("alice", PASSWORD).await;
+    app.login("alice", PASSWORD).await;
+
+    let res = app.get("/auth").await;
+
+    assert_eq!(res.status, StatusCode::OK, "{}", res.text());
+    assert_eq!(res.text(), user_id.to_string());
+    assert!(res.headers["deprecation"]
+        .to_str()
+        .unwrap()
+        .starts_with('@'));
+    assert_eq!(
+        res.headers[header::LINK],
+        r#"</v1/auth>; rel="successor-version""#
+    );
+    assert!(res.headers.get("sunset").is_none());
+}
+
+#[tokio::test]
+async fn unversioned_paths_announce_their_sunset()
+{
+    let mut app = TestApp::builder()
+        .config("api.unversioned_sunset", "2999-01-01T00:00:00Z")
+        .spawn()
+        .await;
+
+    let res = app.get("/auth").await;
+
+    res.assert_error(StatusCode::UNAUTHORIZED, 403);
+    assert_eq!(res.headers["sunset"], "Tue, 01 Jan 2999 00:00:00 GMT");
+}
+
+#[tokio::test]
+async fn unversioned_paths_are_gone_after_their_sunset()
+{
+    let mut app = TestApp::builder()
+        .config("api.unversioned_sunset", "2020-01-01T00:00:00Z")
+        .spawn()
+        .await;
+
+    app.get("/auth").await.assert_error(StatusCode::GONE, 303);
+    app.get("/v1/auth")
+        .await
+        .assert_error(StatusCode::UNAUTHORIZED, 403);
+}
+
+#[tokio::test]
+async fn unversioned_aliases_can_be_turned_off()
+{
+    let mut app = TestApp::builder()
+        .config("api.unversioned_aliases", false)
+        .spawn()
+        .await;
+
+    assert_eq!(app.get("/auth").await.status, StatusCode::NOT_FOUND);
+    app.get("/v1/auth")
+        .await
+        .assert_error(StatusCode::UNAUTHORIZED, 403);
+}